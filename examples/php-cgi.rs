@@ -1,16 +1,12 @@
+//! A web server that redirects every request to a PHP script.
+//!
+//! Usage: php-cgi <php-script-path>
+
 extern crate ascii;
 extern crate tiny_http;
 
 use ascii::AsAsciiStr;
 
-/**!
-
-A web server that redirects every request to a PHP script.
-
-Usage: php-cgi <php-script-path>
-
-*/
-
 fn handle(rq: tiny_http::Request, script: &str) {
     use std::io::Write;
     use std::process::Command;
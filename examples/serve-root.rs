@@ -1,58 +1,17 @@
-use http::HeaderValue;
-use std::fs;
-use std::path::Path;
-
-extern crate ascii;
 extern crate tiny_http;
 
-fn get_content_type(path: &Path) -> &'static str {
-    let extension = match path.extension() {
-        None => return "text/plain",
-        Some(e) => e,
-    };
-
-    match extension.to_str().unwrap() {
-        "gif" => "image/gif",
-        "jpg" => "image/jpeg",
-        "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "pdf" => "application/pdf",
-        "htm" => "text/html; charset=utf8",
-        "html" => "text/html; charset=utf8",
-        "txt" => "text/plain; charset=utf8",
-        _ => "text/plain; charset=utf8",
-    }
-}
-
+/// Serves the files of the current directory.
 fn main() {
     let server = tiny_http::Server::http("0.0.0.0:8000").unwrap();
     let port = server.server_addr().to_ip().unwrap().port();
     println!("Now listening on port {}", port);
 
-    loop {
-        let rq = match server.recv() {
-            Ok(rq) => rq,
-            Err(_) => break,
-        };
+    let files = tiny_http::StaticFiles::new(".")
+        .with_precompressed(true)
+        .with_listing(true);
 
+    for rq in server.incoming_requests() {
         println!("{:?}", rq);
-
-        let url = rq.url().to_string();
-        let path = Path::new(&url);
-        let file = fs::File::open(&path);
-
-        if file.is_ok() {
-            let response = tiny_http::Response::from_file(file.unwrap());
-
-            let response = response.with_header(
-                http::header::CONTENT_TYPE,
-                HeaderValue::from_str(get_content_type(&path)).unwrap(),
-            );
-
-            let _ = rq.respond(response);
-        } else {
-            let rep = tiny_http::Response::new_empty(http::StatusCode::NOT_FOUND);
-            let _ = rq.respond(rep);
-        }
+        let _ = files.serve(rq);
     }
 }
@@ -43,8 +43,8 @@ fn main() {
         );
 
         let response = Response::from_string("hello world");
-        request
-            .respond(response)
-            .unwrap_or(println!("Failed to respond to request"));
+        if request.respond(response).is_err() {
+            println!("Failed to respond to request");
+        }
     }
 }
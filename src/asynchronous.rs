@@ -0,0 +1,170 @@
+//! Receiving and answering requests from async code, with any executor.
+//!
+//! The futures are woken by the threads of the server: receiving a request never blocks, and
+//! the responses are written by the threads that read the requests.
+
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use crate::{Message, Request, Response, Server};
+
+impl Server {
+    /// Same as `recv()`, but returns a future instead of blocking.
+    ///
+    /// ```no_run
+    /// # async fn run() {
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// while let Ok(request) = server.recv_async().await {
+    ///     let response = tiny_http::Response::from_string("hello world");
+    ///     let _ = request.respond_async(response).await;
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn recv_async(&self) -> RecvAsync<'_> {
+        RecvAsync { server: self }
+    }
+
+    /// Same as `incoming_requests()`, but returns a `Stream`.
+    ///
+    /// The stream ends if the server socket is shutdown, or once `unblock()` is called.
+    #[inline]
+    pub fn incoming_async(&self) -> IncomingAsync<'_> {
+        IncomingAsync { server: self }
+    }
+}
+
+/// Future returned by `Server::recv_async()`.
+#[must_use = "futures do nothing unless polled"]
+pub struct RecvAsync<'a> {
+    server: &'a Server,
+}
+
+impl Future for RecvAsync<'_> {
+    type Output = IoResult<Request>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<Request>> {
+        self.server
+            .messages
+            .poll_pop(cx)
+            .map(|message| match message {
+                Some(Message::Error(err)) => Err(err),
+                Some(Message::NewRequest(rq)) => Ok(rq.with_queued(false)),
+                None => Err(IoError::new(IoErrorKind::Other, "thread unblocked")),
+            })
+    }
+}
+
+/// Stream returned by `Server::incoming_async()`.
+#[must_use = "streams do nothing unless polled"]
+pub struct IncomingAsync<'a> {
+    server: &'a Server,
+}
+
+impl Stream for IncomingAsync<'_> {
+    type Item = Request;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Request>> {
+        self.server
+            .messages
+            .poll_pop(cx)
+            .map(|message| match message {
+                Some(Message::NewRequest(rq)) => Some(rq.with_queued(false)),
+                Some(Message::Error(_)) | None => None,
+            })
+    }
+}
+
+impl Request {
+    /// Same as `respond()`, but the response is written by a thread of the server, so that the
+    /// executor isn't blocked while the body is sent.
+    ///
+    /// The returned future resolves once the response has been written. The response is
+    /// written even if the future is dropped. Requests built with `TestRequest` are answered
+    /// right away.
+    pub fn respond_async<R>(self, response: Response<R>) -> RespondAsync
+    where
+        R: Read + Send + 'static,
+    {
+        let completion = Arc::new(Mutex::new(Completion::default()));
+        let completer = Completer(Some(completion.clone()));
+
+        match self.connection_guard().cloned() {
+            Some(guard) => {
+                let mut task = Some((self, response, completer));
+                guard.spawn(Box::new(move || {
+                    if let Some((request, response, completer)) = task.take() {
+                        completer.complete(request.respond(response));
+                    }
+                }));
+            }
+            None => completer.complete(self.respond(response)),
+        }
+
+        RespondAsync { completion }
+    }
+}
+
+/// Future returned by `Request::respond_async()`.
+#[must_use = "futures do nothing unless polled"]
+#[derive(Debug)]
+pub struct RespondAsync {
+    completion: Arc<Mutex<Completion>>,
+}
+
+impl Future for RespondAsync {
+    type Output = IoResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let mut completion = self.completion.lock().unwrap();
+        match completion.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                completion.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Completion {
+    result: Option<IoResult<()>>,
+    waker: Option<Waker>,
+}
+
+// sets the result of a `RespondAsync`, or an error if the response task panicked
+struct Completer(Option<Arc<Mutex<Completion>>>);
+
+impl Completer {
+    fn complete(mut self, result: IoResult<()>) {
+        if let Some(completion) = self.0.take() {
+            Self::set(&completion, result);
+        }
+    }
+
+    fn set(completion: &Mutex<Completion>, result: IoResult<()>) {
+        let waker = {
+            let mut completion = completion.lock().unwrap();
+            completion.result = Some(result);
+            completion.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for Completer {
+    fn drop(&mut self) {
+        if let Some(completion) = self.0.take() {
+            let error = IoError::new(IoErrorKind::Other, "the response could not be written");
+            Self::set(&completion, Err(error));
+        }
+    }
+}
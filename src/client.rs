@@ -4,13 +4,18 @@ use http::{header, HeaderMap, HeaderName, HeaderValue, Method, Version};
 
 use std::io::Error as IoError;
 use std::io::Result as IoResult;
-use std::io::{BufReader, BufWriter, ErrorKind, Read};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
 
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::util::RefinedTcpStream;
+use crate::connection::{ListenAddr, PeerCredentials};
+use crate::hooks::RequestError;
+use crate::util::{ConnectionGuard, RefinedTcpStream};
 use crate::util::{SequentialReader, SequentialReaderBuilder, SequentialWriterBuilder};
-use crate::Request;
+use crate::{ArrivalTime, ConnectionId, Request};
+use crate::{DefaultErrorResponses, ErrorContext, ErrorResponseFactory};
 
 /// A ClientConnection is an object that will store a socket to a client
 /// and return Request objects.
@@ -18,6 +23,12 @@ pub struct ClientConnection {
     // address of the client
     remote_addr: IoResult<Option<SocketAddr>>,
 
+    // address the connection was accepted on
+    local_addr: Option<ListenAddr>,
+
+    // credentials of the peer process, for Unix sockets
+    peer_credentials: Option<PeerCredentials>,
+
     // sequence of Readers to the stream, so that the data is not read in
     //  the wrong order
     source: SequentialReaderBuilder<BufReader<RefinedTcpStream>>,
@@ -34,18 +45,33 @@ pub struct ClientConnection {
 
     // true if the connection goes through SSL
     secure: bool,
+
+    // registration of the connection in the server, to stop reading requests on shutdown
+    guard: Option<Arc<ConnectionGuard>>,
 }
 
 /// Error that can happen when reading a request.
 #[derive(Debug)]
 enum ReadError {
     WrongRequestLine,
-    WrongHeader(Version),
+    WrongHeader(Box<PartialHead>),
     /// the client sent an unrecognized `Expect` header
-    ExpectationFailed(Version),
+    ExpectationFailed(Box<PartialHead>),
+    /// the body uses a `Content-Encoding` that can't be decompressed
+    #[cfg_attr(not(feature = "compression"), allow(dead_code))]
+    UnsupportedContentEncoding(Box<PartialHead>),
     ReadIoError(IoError),
 }
 
+/// The part of a request head that could be read before an error.
+#[derive(Debug)]
+struct PartialHead {
+    method: Method,
+    path: Uri,
+    version: Version,
+    headers: HeaderMap,
+}
+
 impl ClientConnection {
     /// Creates a new `ClientConnection` that takes ownership of the `TcpStream`.
     pub fn new(
@@ -53,6 +79,8 @@ impl ClientConnection {
         mut read_socket: RefinedTcpStream,
     ) -> ClientConnection {
         let remote_addr = read_socket.peer_addr();
+        let local_addr = read_socket.local_addr().ok();
+        let peer_credentials = read_socket.peer_credentials().ok().flatten();
         let secure = read_socket.secure();
 
         let mut source = SequentialReaderBuilder::new(BufReader::with_capacity(1024, read_socket));
@@ -62,17 +90,66 @@ impl ClientConnection {
             source,
             sink: SequentialWriterBuilder::new(BufWriter::with_capacity(1024, write_socket)),
             remote_addr,
+            local_addr,
+            peer_credentials,
             next_header_source: first_header,
             no_more_requests: false,
             secure,
+            guard: None,
         }
     }
 
+    /// Registers the connection, so that the server can stop it.
+    pub(crate) fn with_guard(mut self, guard: ConnectionGuard) -> ClientConnection {
+        self.guard = Some(Arc::new(guard));
+        self
+    }
+
     /// true if the connection is HTTPS
     pub fn secure(&self) -> bool {
         self.secure
     }
 
+    /// Sends the response to a request that couldn't be read, and notifies the hooks of
+    /// the server.
+    fn send_error<W: Write>(
+        &self,
+        writer: W,
+        error: RequestError,
+        version: Version,
+        head: Option<(&Method, &Uri, &HeaderMap)>,
+    ) {
+        let no_headers = HeaderMap::new();
+        let (method, url, headers) = match head {
+            Some((method, url, headers)) => (Some(method), Some(url), headers),
+            None => (None, None, &no_headers),
+        };
+        let context = ErrorContext {
+            error: &error,
+            http_version: version,
+            method,
+            url,
+            headers,
+        };
+
+        let settings = self.guard.as_ref().map(|guard| guard.settings());
+        let response = match settings.and_then(|s| s.error_responses.as_ref()) {
+            Some(factory) => factory.error_response(&context),
+            None => DefaultErrorResponses.error_response(&context),
+        };
+
+        if let Some(ref guard) = self.guard {
+            if let Some(hooks) = guard.hooks() {
+                hooks.request_error(guard.info(), &error);
+                hooks.error_response(guard.info(), response.status_code());
+            }
+        }
+
+        response
+            .raw_print(writer, version, &no_headers, false, None)
+            .ok();
+    }
+
     /// Reads the next line from self.next_header_source.
     ///
     /// Reads until `CRLF` is reached. The next read will start
@@ -82,6 +159,8 @@ impl ClientConnection {
         let mut prev_byte_was_cr = false;
 
         loop {
+            // `next_header_source` wraps a `BufReader`, so reading byte by byte is cheap
+            #[allow(clippy::unbuffered_bytes)]
             let byte = self.next_header_source.by_ref().bytes().next();
 
             let byte = match byte {
@@ -104,10 +183,12 @@ impl ClientConnection {
     /// Reads a request from the stream.
     /// Blocks until the header has been read.
     fn read(&mut self) -> Result<Request, ReadError> {
+        let arrival;
         let (method, path, version, headers) = {
             // reading the request line
             let (method, path, version) = {
                 let line = self.read_next_line().map_err(ReadError::ReadIoError)?;
+                arrival = Instant::now();
 
                 parse_request_line(
                     line.as_str().trim(), // TODO: remove this conversion
@@ -125,12 +206,17 @@ impl ClientConnection {
                     };
 
                     // parse the header from the line
-                    let header = line.as_str().trim();
-                    let wrong_header = || ReadError::WrongHeader(version);
-                    let (name, value) = header.split_once(':').ok_or_else(wrong_header)?;
-                    let name: HeaderName = name.parse().map_err(|_| wrong_header())?;
-                    let value = HeaderValue::from_str(value.trim()).map_err(|_| wrong_header())?;
-                    headers.append(name, value);
+                    match parse_header(line.as_str().trim()) {
+                        Some((name, value)) => headers.append(name, value),
+                        None => {
+                            return Err(ReadError::WrongHeader(Box::new(PartialHead {
+                                method,
+                                path,
+                                version,
+                                headers,
+                            })))
+                        }
+                    };
                 }
 
                 headers
@@ -139,6 +225,31 @@ impl ClientConnection {
             (method, path, version, headers)
         };
 
+        if crate::request::unsupported_expectation(&headers) {
+            return Err(ReadError::ExpectationFailed(Box::new(PartialHead {
+                method,
+                path,
+                version,
+                headers,
+            })));
+        }
+
+        let settings = self.guard.as_ref().map(|guard| guard.settings());
+
+        #[cfg(feature = "compression")]
+        if let Some(config) = settings.and_then(|s| s.request_decompression.as_ref()) {
+            if !config.supports(&headers) {
+                return Err(ReadError::UnsupportedContentEncoding(Box::new(
+                    PartialHead {
+                        method,
+                        path,
+                        version,
+                        headers,
+                    },
+                )));
+            }
+        }
+
         // building the writer for the request
         let writer = self.sink.next().unwrap();
 
@@ -147,25 +258,33 @@ impl ClientConnection {
         std::mem::swap(&mut self.next_header_source, &mut data_source);
 
         // building the next reader
-        let request = crate::request::new_request(
+        let mut request = crate::request::new_request(
             self.secure,
             method,
             path,
-            version.clone(),
+            version,
             headers,
             *self.remote_addr.as_ref().unwrap(),
+            self.local_addr.clone(),
             data_source,
             writer,
+            settings,
         )
-        .map_err(|e| {
-            use crate::request;
-            match e {
-                request::RequestCreationError::CreationIoError(e) => ReadError::ReadIoError(e),
-                request::RequestCreationError::ExpectationFailed => {
-                    ReadError::ExpectationFailed(version)
-                }
+        .map_err(|e| match e {
+            crate::request::RequestCreationError::CreationIoError(e) => ReadError::ReadIoError(e),
+        })?
+        .with_peer_credentials(self.peer_credentials)
+        .with_connection_guard(self.guard.clone());
+
+        // the built-in extensions
+        let extensions = request.extensions_mut();
+        extensions.insert(ArrivalTime(arrival));
+        if let Some(ref guard) = self.guard {
+            extensions.insert(ConnectionId(guard.info().id));
+            if let Some(ref tls) = guard.info().tls {
+                extensions.insert(tls.clone());
             }
-        })?;
+        }
 
         // return the request
         Ok(request)
@@ -178,10 +297,6 @@ impl Iterator for ClientConnection {
     /// Blocks until the next Request is available.
     /// Returns None when no new Requests will come from the client.
     fn next(&mut self) -> Option<Request> {
-        use crate::Response;
-
-        use http::StatusCode;
-
         // the client sent a "connection: close" header in this previous request
         //  or is using HTTP 1.0, meaning that no new request will come
         if self.no_more_requests {
@@ -189,23 +304,40 @@ impl Iterator for ClientConnection {
         }
 
         loop {
-            let rq = match self.read() {
+            // the server is shutting down, don't wait for a new request
+            if let Some(ref guard) = self.guard {
+                if !guard.set_idle(true) {
+                    return None;
+                }
+            }
+
+            let rq = self.read();
+
+            if let Some(ref guard) = self.guard {
+                guard.set_idle(false);
+            }
+
+            let rq = match rq {
                 Err(ReadError::WrongRequestLine) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode::BAD_REQUEST);
-                    response
-                        .raw_print(writer, Version::HTTP_11, &HeaderMap::new(), false, None)
-                        .ok();
+                    self.send_error(
+                        writer,
+                        RequestError::MalformedRequestLine,
+                        Version::HTTP_11,
+                        None,
+                    );
                     return None; // we don't know where the next request would start,
                                  // se we have to close
                 }
 
-                Err(ReadError::WrongHeader(ver)) => {
+                Err(ReadError::WrongHeader(head)) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode::BAD_REQUEST);
-                    response
-                        .raw_print(writer, ver, &HeaderMap::new(), false, None)
-                        .ok();
+                    self.send_error(
+                        writer,
+                        RequestError::MalformedHeader,
+                        head.version,
+                        Some((&head.method, &head.path, &head.headers)),
+                    );
                     return None; // we don't know where the next request would start,
                                  // se we have to close
                 }
@@ -213,22 +345,32 @@ impl Iterator for ClientConnection {
                 Err(ReadError::ReadIoError(ref err)) if err.kind() == ErrorKind::TimedOut => {
                     // request timeout
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode::REQUEST_TIMEOUT);
-                    response
-                        .raw_print(writer, Version::HTTP_11, &HeaderMap::new(), false, None)
-                        .ok();
+                    self.send_error(writer, RequestError::Timeout, Version::HTTP_11, None);
                     return None; // closing the connection
                 }
 
-                Err(ReadError::ExpectationFailed(ver)) => {
+                Err(ReadError::ExpectationFailed(head)) => {
                     let writer = self.sink.next().unwrap();
-                    let response = Response::new_empty(StatusCode::EXPECTATION_FAILED);
-                    response
-                        .raw_print(writer, ver, &HeaderMap::new(), true, None)
-                        .ok();
+                    self.send_error(
+                        writer,
+                        RequestError::UnsupportedExpectation,
+                        head.version,
+                        Some((&head.method, &head.path, &head.headers)),
+                    );
                     return None; // TODO: should be recoverable, but needs handling in case of body
                 }
 
+                Err(ReadError::UnsupportedContentEncoding(head)) => {
+                    let writer = self.sink.next().unwrap();
+                    self.send_error(
+                        writer,
+                        RequestError::UnsupportedContentEncoding,
+                        head.version,
+                        Some((&head.method, &head.path, &head.headers)),
+                    );
+                    return None; // the body wasn't read
+                }
+
                 Err(ReadError::ReadIoError(_)) => return None,
 
                 Ok(rq) => rq,
@@ -236,14 +378,16 @@ impl Iterator for ClientConnection {
 
             // checking HTTP version
             if *rq.http_version() > Version::HTTP_11 {
-                let writer = self.sink.next().unwrap();
-                let response = Response::from_string(
-                    "This server only supports HTTP versions 1.0 and 1.1".to_owned(),
-                )
-                .with_status_code(StatusCode::HTTP_VERSION_NOT_SUPPORTED);
-                response
-                    .raw_print(writer, Version::HTTP_11, &HeaderMap::new(), false, None)
-                    .ok();
+                let error = RequestError::UnsupportedVersion(*rq.http_version());
+                let head = (rq.method().clone(), rq.url().clone(), rq.headers().clone());
+                // answering through the writer of the request, so that it doesn't send a 500
+                let writer = rq.into_writer();
+                self.send_error(
+                    writer,
+                    error,
+                    Version::HTTP_11,
+                    Some((&head.0, &head.1, &head.2)),
+                );
                 continue;
             }
 
@@ -287,6 +431,14 @@ fn parse_http_version(version: &str) -> Result<Version, ReadError> {
     Ok(version)
 }
 
+/// Parses a header line. eg. `Host: example.com`
+fn parse_header(line: &str) -> Option<(HeaderName, HeaderValue)> {
+    let (name, value) = line.split_once(':')?;
+    let name = name.parse().ok()?;
+    let value = HeaderValue::from_str(value.trim()).ok()?;
+    Some((name, value))
+}
+
 /// Parses the request line of the request.
 /// eg. GET / HTTP/1.1
 fn parse_request_line(line: &str) -> Result<(Method, Uri, Version), ReadError> {
@@ -0,0 +1,418 @@
+//! Compression of the responses and decompression of the request bodies, enabled by the
+//! `compression` feature.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::io::{BufReader, Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult};
+
+use flate2::read::{GzEncoder, MultiGzDecoder, ZlibDecoder, ZlibEncoder};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+
+use crate::util;
+use crate::Response;
+
+/// A content coding that responses can be compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    /// `br`, compressed with brotli.
+    Brotli,
+    /// `zstd`, compressed with Zstandard.
+    Zstd,
+    /// `gzip`.
+    Gzip,
+    /// `deflate`, which is the zlib format.
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Returns the name of the coding, as found in the `Accept-Encoding` and
+    /// `Content-Encoding` headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    fn matches(&self, coding: &str) -> bool {
+        coding.eq_ignore_ascii_case(self.as_str())
+            || (*self == ContentEncoding::Gzip && coding.eq_ignore_ascii_case("x-gzip"))
+    }
+}
+
+const ALL_ENCODINGS: [ContentEncoding; 4] = [
+    ContentEncoding::Brotli,
+    ContentEncoding::Zstd,
+    ContentEncoding::Gzip,
+    ContentEncoding::Deflate,
+];
+
+/// When and how the server compresses the responses, see `ServerConfig::compression`.
+///
+/// A response is compressed if the client accepts one of the encodings, if its `Content-Type`
+/// is a textual one (`text/*`, JSON, XML, JavaScript, SVG, ...) and if its length is unknown or
+/// at least the minimum size. Responses that already have a `Content-Encoding`, partial
+/// responses and responses with `Cache-Control: no-transform` are left alone.
+///
+/// Compressed responses get a `Content-Encoding` header and lose their `Content-Length`, so
+/// they are sent in chunks. Every response that could have been compressed gets
+/// `Vary: Accept-Encoding`.
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    encodings: Vec<ContentEncoding>,
+    min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            encodings: ALL_ENCODINGS.to_vec(),
+            min_size: 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Sets the encodings that can be used, by order of preference. The client's preference
+    /// (`q` values of `Accept-Encoding`) comes first. Defaults to brotli, zstd, gzip and
+    /// deflate.
+    pub fn with_encodings<I>(mut self, encodings: I) -> Self
+    where
+        I: IntoIterator<Item = ContentEncoding>,
+    {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Sets the length under which responses are not compressed. Defaults to 1024 bytes.
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Picks the encoding with the highest `q` value in an `Accept-Encoding` header.
+    fn choose(&self, accept_encoding: &str) -> Option<ContentEncoding> {
+        let accepted = util::parse_header_value(accept_encoding);
+        let quality = |encoding: &ContentEncoding| {
+            accepted
+                .iter()
+                .find(|(coding, _)| encoding.matches(coding))
+                .or_else(|| accepted.iter().find(|(coding, _)| *coding == "*"))
+                .map_or(0.0, |(_, q)| *q)
+        };
+
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in &self.encodings {
+            let q = quality(encoding);
+            let better = match best {
+                Some((_, best_q)) => q.partial_cmp(&best_q) == Some(Ordering::Greater),
+                None => q > 0.0,
+            };
+            if better {
+                best = Some((*encoding, q));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+
+    /// Whether the body of a response may be compressed, whatever the client accepts.
+    fn can_compress<R>(&self, response: &Response<R>) -> bool
+    where
+        R: Read,
+    {
+        let headers = response.headers();
+        let status = response.status_code();
+        let no_body = status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED;
+
+        !no_body
+            && status != StatusCode::PARTIAL_CONTENT
+            && !headers.contains_key(header::CONTENT_ENCODING)
+            && !has_token(headers, header::CACHE_CONTROL, "no-transform")
+            && response
+                .data_length()
+                .map_or(true, |len| len >= self.min_size)
+            && headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(is_compressible)
+    }
+}
+
+/// Returns true for the media types that usually gain from compression.
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    let essence = essence.to_ascii_lowercase();
+    let (kind, subtype) = match essence.split_once('/') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    kind == "text"
+        || subtype.ends_with("+json")
+        || subtype.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/javascript"
+                | "application/x-javascript"
+                | "application/ecmascript"
+                | "application/xml"
+                | "application/wasm"
+                | "application/x-ndjson"
+                | "application/graphql"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "image/bmp"
+                | "font/ttf"
+                | "font/otf"
+                | "application/vnd.ms-fontobject"
+        )
+}
+
+/// Whether a comma separated header contains a token, or `*`.
+fn has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|value| value.eq_ignore_ascii_case(token) || value == "*")
+}
+
+/// Body of a response, compressed or not.
+pub(crate) enum Encoder<R: Read> {
+    Identity(R),
+    Brotli(Box<brotli::CompressorReader<R>>),
+    Zstd(zstd::stream::read::Encoder<'static, BufReader<R>>),
+    Gzip(GzEncoder<R>),
+    Deflate(ZlibEncoder<R>),
+    // the compressor couldn't be created, reported when the body is sent
+    Failed(Option<IoError>),
+}
+
+impl<R: Read> Encoder<R> {
+    fn new(reader: R, encoding: ContentEncoding) -> Encoder<R> {
+        // levels that favor speed, since responses are compressed on the fly
+        match encoding {
+            ContentEncoding::Brotli => {
+                Encoder::Brotli(Box::new(brotli::CompressorReader::new(reader, 8192, 4, 22)))
+            }
+            ContentEncoding::Zstd => match zstd::stream::read::Encoder::new(reader, 3) {
+                Ok(encoder) => Encoder::Zstd(encoder),
+                Err(err) => Encoder::Failed(Some(err)),
+            },
+            ContentEncoding::Gzip => {
+                Encoder::Gzip(GzEncoder::new(reader, flate2::Compression::new(6)))
+            }
+            ContentEncoding::Deflate => {
+                Encoder::Deflate(ZlibEncoder::new(reader, flate2::Compression::new(6)))
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for Encoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            Encoder::Identity(reader) => reader.read(buf),
+            Encoder::Brotli(reader) => reader.read(buf),
+            Encoder::Zstd(reader) => reader.read(buf),
+            Encoder::Gzip(reader) => reader.read(buf),
+            Encoder::Deflate(reader) => reader.read(buf),
+            Encoder::Failed(err) => match err.take() {
+                Some(err) => Err(err),
+                None => Ok(0),
+            },
+        }
+    }
+}
+
+/// Compresses a response if the configuration and the request headers allow it.
+pub(crate) fn compress<R>(
+    mut response: Response<R>,
+    config: Option<&CompressionConfig>,
+    request_headers: &HeaderMap,
+) -> Response<Encoder<R>>
+where
+    R: Read,
+{
+    let config = match config {
+        Some(config) if config.can_compress(&response) => config,
+        _ => {
+            let length = response.data_length();
+            return response.map_data(Encoder::Identity, length);
+        }
+    };
+
+    if !has_token(response.headers(), header::VARY, "accept-encoding") {
+        response.add_header(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+
+    let encoding = request_headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| config.choose(value));
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => {
+            let length = response.data_length();
+            return response.map_data(Encoder::Identity, length);
+        }
+    };
+
+    // the compressed body is a different representation, which can't share a strong validator
+    let headers = response.headers_mut();
+    if let Some(etag) = headers.get(header::ETAG) {
+        if etag.as_bytes().starts_with(b"\"") {
+            let mut weak = b"W/".to_vec();
+            weak.extend_from_slice(etag.as_bytes());
+            if let Ok(weak) = HeaderValue::from_bytes(&weak) {
+                headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    // ranges would be counted in the uncompressed data
+    headers.remove(header::ACCEPT_RANGES);
+
+    response.map_data(|reader| Encoder::new(reader, encoding), None)
+}
+
+/// How the server decompresses the bodies of the requests, see
+/// `ServerConfig::request_decompression`.
+///
+/// The body of a request with a `Content-Encoding` is decompressed as it is read from
+/// `Request::as_reader()`, and the `Content-Encoding` and `Content-Length` headers are removed
+/// from the request. Requests encoded with anything else than the allowed encodings are
+/// answered with `415 Unsupported Media Type` by the server.
+///
+/// Reading more than the maximum size from a decompressed body fails with an error of kind
+/// `InvalidData`, which protects from small bodies that decompress to huge amounts of data.
+#[derive(Debug, Clone)]
+pub struct DecompressionConfig {
+    encodings: Vec<ContentEncoding>,
+    max_size: u64,
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        DecompressionConfig {
+            encodings: ALL_ENCODINGS.to_vec(),
+            max_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl DecompressionConfig {
+    /// Sets the encodings that the clients may use. Defaults to brotli, zstd, gzip and deflate.
+    pub fn with_encodings<I>(mut self, encodings: I) -> Self
+    where
+        I: IntoIterator<Item = ContentEncoding>,
+    {
+        self.encodings = encodings.into_iter().collect();
+        self
+    }
+
+    /// Sets the maximum size of a decompressed body. Defaults to 16 MiB.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Returns the encodings of a request, in the order they were applied, or `None` if one of
+    /// them is not allowed.
+    fn encodings(&self, headers: &HeaderMap) -> Option<Vec<ContentEncoding>> {
+        headers
+            .get_all(header::CONTENT_ENCODING)
+            .iter()
+            .flat_map(|value| value.to_str().unwrap_or(",").split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.eq_ignore_ascii_case("identity"))
+            .map(|coding| {
+                self.encodings
+                    .iter()
+                    .find(|encoding| encoding.matches(coding))
+                    .copied()
+            })
+            .collect()
+    }
+
+    /// Returns true if the body of a request can be decompressed.
+    pub(crate) fn supports(&self, headers: &HeaderMap) -> bool {
+        self.encodings(headers).is_some()
+    }
+
+    /// Returns true if the body of a request is encoded with something else than `identity`.
+    pub(crate) fn is_encoded(&self, headers: &HeaderMap) -> bool {
+        self.encodings(headers)
+            .map_or(true, |encodings| !encodings.is_empty())
+    }
+
+    /// Wraps the body of an encoded request into the decoders, and removes the headers that
+    /// describe the encoded body.
+    pub(crate) fn decode(
+        &self,
+        mut reader: Box<dyn Read + Send>,
+        headers: &mut HeaderMap,
+    ) -> IoResult<Box<dyn Read + Send>> {
+        let encodings = self.encodings(headers).ok_or_else(|| {
+            IoError::new(IoErrorKind::InvalidData, "unsupported Content-Encoding")
+        })?;
+
+        for encoding in encodings.iter().rev() {
+            reader = match encoding {
+                ContentEncoding::Brotli => Box::new(brotli::Decompressor::new(reader, 8192)),
+                ContentEncoding::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+                ContentEncoding::Gzip => Box::new(MultiGzDecoder::new(reader)),
+                ContentEncoding::Deflate => Box::new(ZlibDecoder::new(reader)),
+            };
+        }
+
+        headers.remove(header::CONTENT_ENCODING);
+        headers.remove(header::CONTENT_LENGTH);
+        Ok(Box::new(LimitedReader {
+            inner: reader,
+            remaining: self.max_size,
+        }))
+    }
+}
+
+/// Fails once more than a given number of bytes are read.
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            // only an error if there was more to read
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    "the decompressed body is too large",
+                )),
+            };
+        }
+
+        let len = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
@@ -0,0 +1,214 @@
+//! Conditional requests (RFC 9110, section 13), evaluated against the `ETag` and
+//! `Last-Modified` headers of the responses.
+
+use std::io::Read;
+use std::time::SystemTime;
+
+use http::{header, HeaderMap, HeaderName, Method, StatusCode};
+use httpdate::HttpDate;
+
+use crate::Response;
+
+/// Builds a strong entity tag from the data of a response.
+pub(crate) fn etag_of_data(data: &[u8]) -> String {
+    // FNV-1a, which stays the same across restarts and versions of Rust
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("\"{:016x}\"", hash)
+}
+
+/// Builds a strong entity tag from the modification time and the length of a file.
+pub(crate) fn etag_of_file(modified: SystemTime, length: u64) -> String {
+    let since_epoch = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "\"{:x}.{:x}-{:x}\"",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos(),
+        length
+    )
+}
+
+/// An entity tag, with its quotes.
+struct EntityTag<'a> {
+    weak: bool,
+    tag: &'a str,
+}
+
+impl<'a> EntityTag<'a> {
+    fn parse(value: &'a str) -> Option<EntityTag<'a>> {
+        let (weak, tag) = match value.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, value),
+        };
+        if tag.len() < 2 || !tag.starts_with('"') || !tag.ends_with('"') {
+            return None;
+        }
+        Some(EntityTag { weak, tag })
+    }
+}
+
+fn strong_eq(a: &EntityTag<'_>, b: &EntityTag<'_>) -> bool {
+    !a.weak && !b.weak && a.tag == b.tag
+}
+
+fn weak_eq(a: &EntityTag<'_>, b: &EntityTag<'_>) -> bool {
+    a.tag == b.tag
+}
+
+/// Splits a list of entity tags, whose values may contain commas.
+fn split_entity_tags(value: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                tags.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    tags.push(value[start..].trim());
+    tags.retain(|tag| !tag.is_empty());
+    tags
+}
+
+/// Whether a `If-Match` or `If-None-Match` header matches the entity tag of the resource.
+fn matches(
+    request_headers: &HeaderMap,
+    name: HeaderName,
+    exists: bool,
+    etag: Option<&EntityTag<'_>>,
+    compare: fn(&EntityTag<'_>, &EntityTag<'_>) -> bool,
+) -> Option<bool> {
+    let mut values = request_headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .peekable();
+    values.peek()?;
+
+    let mut found = false;
+    for tag in values.flat_map(split_entity_tags) {
+        found |= match (tag, etag) {
+            ("*", _) => exists,
+            (tag, Some(etag)) => EntityTag::parse(tag).is_some_and(|tag| compare(&tag, etag)),
+            (_, None) => false,
+        };
+    }
+    Some(found)
+}
+
+fn date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse::<HttpDate>()
+        .ok()
+        .map(SystemTime::from)
+}
+
+/// Evaluates the preconditions of a request against the current state of the resource, in the
+/// order of RFC 9110, section 13.2.2. Returns the status to answer with instead of performing
+/// the method, if any.
+pub(crate) fn check(
+    method: &Method,
+    request_headers: &HeaderMap,
+    exists: bool,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Option<StatusCode> {
+    let etag = etag.and_then(EntityTag::parse);
+
+    let if_match = matches(
+        request_headers,
+        header::IF_MATCH,
+        exists,
+        etag.as_ref(),
+        strong_eq,
+    );
+    let precondition_failed = match if_match {
+        Some(found) => !found,
+        None => match (
+            date(request_headers, header::IF_UNMODIFIED_SINCE),
+            last_modified,
+        ) {
+            (Some(since), Some(modified)) => modified > since,
+            _ => false,
+        },
+    };
+    if precondition_failed {
+        return Some(StatusCode::PRECONDITION_FAILED);
+    }
+
+    let safe = *method == Method::GET || *method == Method::HEAD;
+    let if_none_match = matches(
+        request_headers,
+        header::IF_NONE_MATCH,
+        exists,
+        etag.as_ref(),
+        weak_eq,
+    );
+    let not_modified = match if_none_match {
+        Some(true) if !safe => return Some(StatusCode::PRECONDITION_FAILED),
+        Some(found) => found,
+        None if safe => match (
+            date(request_headers, header::IF_MODIFIED_SINCE),
+            last_modified,
+        ) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        },
+        None => false,
+    };
+    if not_modified {
+        return Some(StatusCode::NOT_MODIFIED);
+    }
+    None
+}
+
+/// Answers `GET` and `HEAD` requests with `304 Not Modified` or `412 Precondition Failed` when
+/// their preconditions say so, for the responses whose validators were computed by the library.
+///
+/// The other methods change the resource before the response is built, so it is too late to
+/// check their preconditions here, see `Request::check_preconditions()`.
+pub(crate) fn evaluate<R>(
+    response: Response<R>,
+    method: &Method,
+    request_headers: &HeaderMap,
+) -> Response<R>
+where
+    R: Read,
+{
+    let safe = *method == Method::GET || *method == Method::HEAD;
+    // only the responses that would be sent successfully are conditional
+    if !safe || !response.is_conditional() || !response.status_code().is_success() {
+        return response;
+    }
+
+    let headers = response.headers();
+    let etag = headers
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok());
+    let last_modified = date(headers, header::LAST_MODIFIED);
+
+    match check(method, request_headers, true, etag, last_modified) {
+        Some(StatusCode::NOT_MODIFIED) => {
+            // the body is not sent by `raw_print()`, but its length is still announced
+            let length = response.data_length();
+            response
+                .with_status_code(StatusCode::NOT_MODIFIED)
+                .map_data(|reader| reader, length)
+        }
+        Some(status) => response
+            .with_status_code(status)
+            .map_data(|reader| reader, Some(0)),
+        None => response,
+    }
+}
@@ -1,12 +1,19 @@
 //! Abstractions of Tcp and Unix socket types
 
+#[cfg(target_os = "linux")]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
 #[cfg(unix)]
 use std::os::unix::net as unix_net;
 use std::{
     net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
     path::PathBuf,
+    time::Duration,
 };
 
+use crate::log;
+
 /// Unified listener. Either a [`TcpListener`] or [`std::os::unix::net::UnixListener`]
 pub enum Listener {
     Tcp(TcpListener),
@@ -31,6 +38,93 @@ impl Listener {
             Self::Unix(l) => l.accept().map(|(conn, _)| (Connection::from(conn), None)),
         }
     }
+
+    /// Creates a new handle to the same listening socket.
+    pub fn try_clone(&self) -> std::io::Result<Listener> {
+        match self {
+            Self::Tcp(l) => l.try_clone().map(Listener::from),
+            #[cfg(unix)]
+            Self::Unix(l) => l.try_clone().map(Listener::from),
+        }
+    }
+
+    /// Blocks until a client can be accepted, or until `interrupt` becomes readable.
+    ///
+    /// Returns `false` in the latter case.
+    #[cfg(unix)]
+    pub(crate) fn wait_for_client(&self, interrupt: BorrowedFd<'_>) -> std::io::Result<bool> {
+        use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+
+        let mut fds = [
+            PollFd::new(self.as_fd(), PollFlags::POLLIN),
+            PollFd::new(interrupt, PollFlags::POLLIN),
+        ];
+        loop {
+            match poll(&mut fds, PollTimeout::NONE) {
+                Ok(_) => break,
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        // an error on the listener is reported by the following `accept()`
+        Ok(fds[1].revents().map_or(true, |events| events.is_empty()))
+    }
+
+    /// Turns a file descriptor into a `Listener`, checking that it is a listening TCP or
+    /// UNIX stream socket.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn from_owned_fd(fd: OwnedFd) -> std::io::Result<Listener> {
+        if Self::check_fd(fd.as_fd())? {
+            Ok(Listener::from(unix_net::UnixListener::from(fd)))
+        } else {
+            Ok(Listener::from(TcpListener::from(fd)))
+        }
+    }
+
+    /// Checks that a file descriptor is a listening TCP or UNIX stream socket, and returns
+    /// whether it is a UNIX one.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn check_fd(fd: BorrowedFd<'_>) -> std::io::Result<bool> {
+        use nix::sys::socket::{
+            getsockname, getsockopt, sockopt, AddressFamily, SockType, SockaddrLike,
+            SockaddrStorage,
+        };
+
+        let not_a_listener = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "File descriptor is not a listening stream socket",
+            )
+        };
+
+        if getsockopt(&fd, sockopt::SockType)? != SockType::Stream
+            || !getsockopt(&fd, sockopt::AcceptConn)?
+        {
+            return Err(not_a_listener());
+        }
+
+        match getsockname::<SockaddrStorage>(fd.as_raw_fd())?.family() {
+            Some(AddressFamily::Inet) | Some(AddressFamily::Inet6) => Ok(false),
+            Some(AddressFamily::Unix) => Ok(true),
+            _ => Err(not_a_listener()),
+        }
+    }
+}
+#[cfg(unix)]
+impl AsFd for Listener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            Self::Tcp(l) => l.as_fd(),
+            Self::Unix(l) => l.as_fd(),
+        }
+    }
+}
+#[cfg(unix)]
+impl AsRawFd for Listener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.as_fd().as_raw_fd()
+    }
 }
 impl From<TcpListener> for Listener {
     fn from(s: TcpListener) -> Self {
@@ -87,6 +181,34 @@ impl Connection {
         }
     }
 
+    /// Gets the credentials of the peer process. Only available for Unix sockets on Linux.
+    pub(crate) fn peer_credentials(&self) -> std::io::Result<Option<PeerCredentials>> {
+        match self {
+            Self::Tcp(_) => Ok(None),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Self::Unix(s) => {
+                use nix::sys::socket::{getsockopt, sockopt};
+                let cred = getsockopt(s, sockopt::PeerCredentials)?;
+                Ok(Some(PeerCredentials {
+                    pid: cred.pid(),
+                    uid: cred.uid(),
+                    gid: cred.gid(),
+                }))
+            }
+            #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+            Self::Unix(_) => Ok(None),
+        }
+    }
+
+    /// Gets the local address the connection was accepted on.
+    pub(crate) fn local_addr(&self) -> std::io::Result<ListenAddr> {
+        match self {
+            Self::Tcp(s) => s.local_addr().map(ListenAddr::from),
+            #[cfg(unix)]
+            Self::Unix(s) => s.local_addr().map(ListenAddr::from),
+        }
+    }
+
     pub(crate) fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
         match self {
             Self::Tcp(s) => s.shutdown(how),
@@ -95,6 +217,32 @@ impl Connection {
         }
     }
 
+    /// Applies the options relevant to an accepted connection.
+    pub(crate) fn apply_options(&self, options: &SocketOptions) -> std::io::Result<()> {
+        let socket = match self {
+            Self::Tcp(s) => {
+                if options.nodelay {
+                    s.set_nodelay(true)?;
+                }
+                let socket = socket2::SockRef::from(s);
+                if let Some(time) = options.keepalive {
+                    socket.set_tcp_keepalive(&socket2::TcpKeepalive::new().with_time(time))?;
+                }
+                socket
+            }
+            #[cfg(unix)]
+            Self::Unix(s) => socket2::SockRef::from(s),
+        };
+
+        if let Some(size) = options.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = options.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn try_clone(&self) -> std::io::Result<Self> {
         match self {
             Self::Tcp(s) => s.try_clone().map(Self::from),
@@ -115,12 +263,19 @@ impl From<unix_net::UnixStream> for Connection {
     }
 }
 
+/// Address a listener should be bound to.
 #[derive(Debug, Clone)]
 pub enum ConfigListenAddr {
+    /// A list of candidate addresses, as produced by [`ToSocketAddrs`]. They are tried in
+    /// order and only the first one that can be bound is used; to listen on several
+    /// addresses at once, configure one [`ListenerConfig`](crate::ListenerConfig) per address.
     IP(Vec<SocketAddr>),
     #[cfg(unix)]
-    // TODO: use SocketAddr when bind_addr is stabilized
     Unix(std::path::PathBuf),
+    /// A name in the Linux abstract socket namespace. Such sockets have no file on disk and
+    /// disappear as soon as they are closed.
+    #[cfg(target_os = "linux")]
+    UnixAbstract(Vec<u8>),
 }
 impl ConfigListenAddr {
     pub fn from_socket_addrs<A: ToSocketAddrs>(addrs: A) -> std::io::Result<Self> {
@@ -132,11 +287,259 @@ impl ConfigListenAddr {
         Self::Unix(path.into())
     }
 
-    pub(crate) fn bind(&self) -> std::io::Result<Listener> {
-        match self {
-            Self::IP(a) => TcpListener::bind(a.as_slice()).map(Listener::from),
+    /// Builds an address in the Linux abstract socket namespace.
+    ///
+    /// The name must not contain the leading NUL byte, it is added automatically.
+    #[cfg(target_os = "linux")]
+    pub fn unix_abstract<N: Into<Vec<u8>>>(name: N) -> Self {
+        Self::UnixAbstract(name.into())
+    }
+
+    pub(crate) fn bind(&self, options: &SocketOptions) -> std::io::Result<Listener> {
+        let listener = match self {
+            Self::IP(a) => return bind_tcp(a, options).map(Listener::from),
             #[cfg(unix)]
-            Self::Unix(a) => unix_net::UnixListener::bind(a).map(Listener::from),
+            Self::Unix(a) => unix_net::UnixListener::bind(a)?,
+            #[cfg(target_os = "linux")]
+            Self::UnixAbstract(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = unix_net::SocketAddr::from_abstract_name(name)?;
+                unix_net::UnixListener::bind_addr(&addr)?
+            }
+        };
+
+        #[cfg(unix)]
+        {
+            // listening again only updates the size of the queue
+            if let Some(backlog) = options.backlog {
+                socket2::SockRef::from(&listener).listen(backlog)?;
+            }
+            Ok(Listener::from(listener))
+        }
+    }
+
+    /// Same as `bind()`, but applies the lifecycle options to UNIX sockets bound to a path.
+    #[cfg(unix)]
+    pub(crate) fn bind_with_options(
+        &self,
+        options: &SocketOptions,
+        unix_options: &UnixSocketOptions,
+    ) -> std::io::Result<Listener> {
+        use socket2::{Domain, SockAddr, Socket, Type};
+
+        let path = match self {
+            Self::Unix(path) => path,
+            _ => return self.bind(options),
+        };
+
+        if unix_options.remove_stale {
+            remove_stale_socket(path)?;
+        }
+
+        let socket = Socket::new(Domain::UNIX, Type::STREAM, None)?;
+        socket.bind(&SockAddr::unix(path)?)?;
+
+        // clients can't connect before `listen()`, so the socket file gets its owner and
+        // permissions before anybody can reach it
+        let prepare = || -> std::io::Result<()> {
+            if unix_options.owner.is_some() || unix_options.group.is_some() {
+                nix::unistd::chown(
+                    path,
+                    unix_options.owner.map(nix::unistd::Uid::from_raw),
+                    unix_options.group.map(nix::unistd::Gid::from_raw),
+                )?;
+            }
+            if let Some(mode) = unix_options.mode {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+            }
+            socket.listen(options.backlog.unwrap_or(DEFAULT_BACKLOG))
+        };
+        if let Err(err) = prepare() {
+            let _ = std::fs::remove_file(path);
+            return Err(err);
+        }
+
+        let listener = unix_net::UnixListener::from(std::os::fd::OwnedFd::from(socket));
+        Ok(Listener::from(listener))
+    }
+}
+
+/// Binds a TCP listener to the first address that accepts it, applying the socket options
+/// that must be set before listening.
+fn bind_tcp(addrs: &[SocketAddr], options: &SocketOptions) -> std::io::Result<TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    if options.backlog.is_none()
+        && options.recv_buffer_size.is_none()
+        && options.send_buffer_size.is_none()
+    {
+        return TcpListener::bind(addrs);
+    }
+
+    let bind = |addr: &SocketAddr| -> std::io::Result<TcpListener> {
+        let socket = Socket::new(
+            Domain::for_address(*addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        // same as `TcpListener::bind()`
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        if let Some(size) = options.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = options.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        socket.bind(&(*addr).into())?;
+        socket.listen(options.backlog.unwrap_or(DEFAULT_BACKLOG))?;
+        Ok(socket.into())
+    };
+
+    let mut last_error = None;
+    for addr in addrs {
+        match bind(addr) {
+            Ok(listener) => return Ok(listener),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    }))
+}
+
+/// Size of the queue of pending connections used by the standard library.
+const DEFAULT_BACKLOG: i32 = 128;
+
+/// Options applied to the listening sockets and to every connection they accept.
+///
+/// Options that have no meaning for UNIX sockets (`nodelay` and `keepalive`) are ignored
+/// for them. The default leaves every option to the operating system.
+#[derive(Debug, Clone, Default)]
+pub struct SocketOptions {
+    /// If `true`, sets `TCP_NODELAY` on accepted connections, so that small responses are
+    /// sent right away instead of being delayed by Nagle's algorithm.
+    pub nodelay: bool,
+
+    /// If `Some`, enables `SO_KEEPALIVE` on accepted connections, with the given idle time
+    /// before the first probe is sent.
+    pub keepalive: Option<Duration>,
+
+    /// Size of the kernel receive buffer (`SO_RCVBUF`).
+    pub recv_buffer_size: Option<usize>,
+
+    /// Size of the kernel send buffer (`SO_SNDBUF`).
+    pub send_buffer_size: Option<usize>,
+
+    /// Maximum length of the queue of connections waiting to be accepted. Defaults to 128.
+    pub backlog: Option<i32>,
+}
+
+/// Options controlling the lifecycle of UNIX listening sockets bound to a path.
+///
+/// They have no effect on TCP listeners nor on abstract UNIX addresses.
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixSocketOptions {
+    /// If `true`, an existing socket file at the path is removed before binding, but only
+    /// after checking that no server accepts connections on it anymore. A file that is not
+    /// a socket is never removed. Defaults to `false`.
+    pub remove_stale: bool,
+
+    /// Permissions to set on the socket file, for example `0o660`. They are set before the
+    /// socket starts listening, so that no client can connect with the default permissions.
+    pub mode: Option<u32>,
+
+    /// User ID to set as the owner of the socket file, before the socket starts listening.
+    pub owner: Option<u32>,
+
+    /// Group ID to set on the socket file, before the socket starts listening.
+    pub group: Option<u32>,
+
+    /// If `true`, the socket file is removed when the `Server` is dropped, unless another
+    /// socket has been bound at the same path in the meantime. Defaults to `true`.
+    pub remove_on_drop: bool,
+}
+
+#[cfg(unix)]
+impl Default for UnixSocketOptions {
+    fn default() -> Self {
+        UnixSocketOptions {
+            remove_stale: false,
+            mode: None,
+            owner: None,
+            group: None,
+            remove_on_drop: true,
+        }
+    }
+}
+
+/// Removes the socket file at `path` if nothing is listening on it anymore.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => (),
+        // let `bind` report the error for anything that isn't a socket
+        Ok(_) => return Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    }
+
+    match unix_net::UnixStream::connect(path) {
+        Ok(_) => Err(Error::new(
+            ErrorKind::AddrInUse,
+            format!("{} is in use by a running server", path.display()),
+        )),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            log::debug!("Removing stale socket {}", path.display());
+            std::fs::remove_file(path)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// A socket file created by a UNIX listener, identified by its inode so that a file
+/// replaced by another server is left alone.
+#[cfg(unix)]
+pub(crate) struct SocketFile {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+#[cfg(unix)]
+impl SocketFile {
+    /// Records the socket file a listener is bound to, if any.
+    pub(crate) fn of(listener: &Listener) -> Option<SocketFile> {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = match listener {
+            Listener::Unix(l) => l.local_addr().ok()?.as_pathname()?.to_path_buf(),
+            Listener::Tcp(_) => return None,
+        };
+        let metadata = std::fs::symlink_metadata(&path).ok()?;
+        Some(SocketFile {
+            path,
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+
+    /// Removes the socket file, unless it has been replaced since it was recorded.
+    pub(crate) fn remove(&self) {
+        use std::os::unix::fs::MetadataExt;
+
+        if let Ok(metadata) = std::fs::symlink_metadata(&self.path) {
+            if metadata.dev() == self.dev && metadata.ino() == self.ino {
+                let _ = std::fs::remove_file(&self.path);
+            }
         }
     }
 }
@@ -192,3 +595,17 @@ impl std::fmt::Display for ListenAddr {
         }
     }
 }
+
+/// Credentials of the process at the other end of a Unix socket.
+///
+/// These are captured by the kernel when the client connects (`SO_PEERCRED`), and are
+/// only available on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerCredentials {
+    /// Process ID of the peer. May be `0` if the peer lives in a different PID namespace.
+    pub pid: i32,
+    /// Effective user ID of the peer.
+    pub uid: u32,
+    /// Effective group ID of the peer.
+    pub gid: u32,
+}
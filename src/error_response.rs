@@ -0,0 +1,89 @@
+//! Responses sent by the server on its own, when a request can't be read.
+
+use std::fmt;
+
+use http::{HeaderMap, Method, StatusCode, Uri, Version};
+
+use crate::{RequestError, Response, ResponseBox};
+
+/// Builds the responses sent when a request can't be read.
+///
+/// It is implemented for closures, so that a function can be used directly:
+///
+/// ```
+/// use std::sync::Arc;
+/// use tiny_http::{ErrorContext, Response, ResponseBox};
+///
+/// let config = tiny_http::ServerConfig {
+///     error_responses: Some(Arc::new(|context: &ErrorContext<'_>| -> ResponseBox {
+///         let body = format!("{{\"error\":\"{}\"}}", context.error);
+///         Response::from_string(body)
+///             .with_status_code(context.error.status_code())
+///             .with_header(
+///                 http::header::CONTENT_TYPE,
+///                 http::HeaderValue::from_static("application/json"),
+///             )
+///             .boxed()
+///     })),
+///     ..Default::default()
+/// };
+/// ```
+pub trait ErrorResponseFactory: Send + Sync {
+    /// Returns the response to send for the given error.
+    ///
+    /// The connection is closed after the response, except for `UnsupportedVersion` errors.
+    fn error_response(&self, context: &ErrorContext<'_>) -> ResponseBox;
+}
+
+impl<F> ErrorResponseFactory for F
+where
+    F: Fn(&ErrorContext<'_>) -> ResponseBox + Send + Sync,
+{
+    fn error_response(&self, context: &ErrorContext<'_>) -> ResponseBox {
+        self(context)
+    }
+}
+
+impl fmt::Debug for dyn ErrorResponseFactory {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ErrorResponseFactory")
+    }
+}
+
+/// What is known about a request that couldn't be read.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ErrorContext<'a> {
+    /// Why the request couldn't be read.
+    pub error: &'a RequestError,
+
+    /// HTTP version the response will be sent with.
+    pub http_version: Version,
+
+    /// Method of the request, if the request line could be parsed.
+    pub method: Option<&'a Method>,
+
+    /// URL of the request, if the request line could be parsed.
+    pub url: Option<&'a Uri>,
+
+    /// The headers that could be parsed, which may be only some of them.
+    pub headers: &'a HeaderMap,
+}
+
+/// The error responses sent by default: empty, except for a short text explaining which
+/// HTTP versions are supported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultErrorResponses;
+
+impl ErrorResponseFactory for DefaultErrorResponses {
+    fn error_response(&self, context: &ErrorContext<'_>) -> ResponseBox {
+        match context.error {
+            RequestError::UnsupportedVersion(_) => Response::from_string(
+                "This server only supports HTTP versions 1.0 and 1.1".to_owned(),
+            )
+            .with_status_code(StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+            .boxed(),
+            error => Response::new_empty(error.status_code()).boxed(),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Values stored by the server in the extensions of every request.
+
+use std::time::Instant;
+
+/// Identifier of the connection a request was received on, stored in `Request::extensions()`.
+///
+/// This is the same as `ConnectionInfo::id`, so that requests can be matched with the events
+/// of `ServerHooks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionId(pub usize);
+
+/// When the request line of a request was received, stored in `Request::extensions()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArrivalTime(pub Instant);
+
+/// What was negotiated during the SSL handshake of a connection.
+///
+/// It is stored in `Request::extensions()` for the requests received over SSL, and in
+/// `ConnectionInfo::tls`. Not every SSL implementation reports everything: `ssl-native-tls`
+/// leaves every field empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TlsInfo {
+    /// Version of the protocol, such as `TLSv1.3`.
+    pub protocol_version: Option<String>,
+
+    /// Name of the cipher suite, as spelled by the SSL implementation.
+    pub cipher_suite: Option<String>,
+
+    /// Server name sent by the client with SNI.
+    pub server_name: Option<String>,
+
+    /// Protocol negotiated with ALPN.
+    pub alpn_protocol: Option<Vec<u8>>,
+}
@@ -0,0 +1,115 @@
+//! Handing the listening sockets over to another process.
+//!
+//! This allows restarting a server without refusing any connection: the new process receives
+//! the sockets and starts accepting on them, while the old one stops accepting and finishes
+//! the requests it already has. The sockets are sent as `SCM_RIGHTS` ancillary data over a
+//! UNIX stream socket, preceded by their number.
+
+use std::io::{Error, ErrorKind, IoSlice, IoSliceMut, Result as IoResult};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+
+use crate::util::fd;
+use crate::{Listener, Server};
+
+/// Maximum number of listeners that can be handed over at once.
+const MAX_LISTENERS: usize = 64;
+
+impl Server {
+    /// Sends the listening sockets of the server to another process through `socket`.
+    ///
+    /// The other process gets them back with `Listener::receive_listeners()`, in the order of
+    /// `server_addrs()`, and can build its own server with `Server::from_listeners()`, or
+    /// `Server::from_listeners_with_config()` to keep its settings. SSL configurations are not
+    /// transferred.
+    ///
+    /// This server keeps accepting connections until `stop_accepting()` is called, so that no
+    /// connection is refused while the other process starts. From now on, the socket files of
+    /// UNIX listeners are left in place when the server is dropped, since they are now used by
+    /// the other process.
+    ///
+    /// A typical restart looks like this:
+    ///
+    /// ```no_run
+    /// # use std::time::{Duration, Instant};
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// # let socket = std::os::unix::net::UnixStream::connect("/run/app/handoff.sock").unwrap();
+    /// server.send_listeners(&socket).unwrap();
+    /// server.stop_accepting();
+    ///
+    /// // answer the requests that were already received, for at most 30 seconds
+    /// let deadline = Instant::now() + Duration::from_secs(30);
+    /// while server.num_connections() > 0 && Instant::now() < deadline {
+    ///     if let Ok(Some(request)) = server.recv_timeout(Duration::from_millis(100)) {
+    ///         // ...
+    ///         # let _ = request;
+    ///     }
+    /// }
+    /// ```
+    pub fn send_listeners(&self, socket: &UnixStream) -> IoResult<()> {
+        let fds: Vec<RawFd> = self.listeners.iter().map(AsRawFd::as_raw_fd).collect();
+        if fds.len() > MAX_LISTENERS {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("At most {} listeners can be handed over", MAX_LISTENERS),
+            ));
+        }
+
+        let count = (fds.len() as u32).to_le_bytes();
+        let iov = [IoSlice::new(&count)];
+        let cmsgs = [ControlMessage::ScmRights(&fds)];
+        let sent = sendmsg::<()>(socket.as_raw_fd(), &iov, &cmsgs, MsgFlags::empty(), None)?;
+        if sent != count.len() {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                "Failed to send the number of listeners",
+            ));
+        }
+
+        self.socket_files.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+impl Listener {
+    /// Receives the listening sockets sent by `Server::send_listeners()` through `socket`.
+    ///
+    /// Blocks until they are available. The listeners are returned in the order of the
+    /// sending server's `server_addrs()`, and are marked close-on-exec.
+    pub fn receive_listeners(socket: &UnixStream) -> IoResult<Vec<Listener>> {
+        let mut count = [0; 4];
+        let mut iov = [IoSliceMut::new(&mut count)];
+        let mut cmsg_buffer = nix::cmsg_space!([RawFd; MAX_LISTENERS]);
+        let msg = recvmsg::<()>(
+            socket.as_raw_fd(),
+            &mut iov,
+            Some(&mut cmsg_buffer),
+            MsgFlags::MSG_CMSG_CLOEXEC,
+        )?;
+        let received = msg.bytes;
+
+        let mut fds = Vec::new();
+        for cmsg in msg.cmsgs()? {
+            if let ControlMessageOwned::ScmRights(raw_fds) = cmsg {
+                fds.extend(fd::claim_received(raw_fds));
+            }
+        }
+
+        if received != count.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Failed to receive the number of listeners",
+            ));
+        }
+        if u32::from_le_bytes(count) as usize != fds.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The number of listeners received doesn't match the number announced",
+            ));
+        }
+
+        fds.into_iter().map(Listener::from_owned_fd).collect()
+    }
+}
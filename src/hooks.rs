@@ -0,0 +1,197 @@
+//! Callbacks to observe what the server does on its own, for logging or metrics.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Error as IoError;
+use std::net::SocketAddr;
+
+use http::{Method, StatusCode, Uri, Version};
+
+use crate::{ListenAddr, Request, TlsInfo};
+
+/// Receives the events of a `Server`.
+///
+/// Every method does nothing by default, so that an implementation only has to override the
+/// events it is interested in. The methods are called from the threads of the server, and
+/// should return quickly.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Metrics {
+///     parse_errors: AtomicUsize,
+/// }
+///
+/// impl tiny_http::ServerHooks for Metrics {
+///     fn request_error(
+///         &self,
+///         _connection: &tiny_http::ConnectionInfo,
+///         _error: &tiny_http::RequestError,
+///     ) {
+///         self.parse_errors.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let config = tiny_http::ServerConfig {
+///     hooks: Some(Arc::new(Metrics::default())),
+///     ..Default::default()
+/// };
+/// ```
+pub trait ServerHooks: Send + Sync {
+    /// Called when a connection has been accepted, after the SSL handshake if any.
+    fn connection_opened(&self, _connection: &ConnectionInfo) {}
+
+    /// Called when a connection is closed, once its last request has been answered.
+    ///
+    /// Connections whose request was turned into a writer or upgraded are reported when the
+    /// `Request` is consumed, even though the socket stays open.
+    fn connection_closed(&self, _connection: &ConnectionInfo) {}
+
+    /// Called when the SSL handshake with a new client fails. The connection is then closed.
+    fn tls_handshake_failed(
+        &self,
+        _listener_index: usize,
+        _remote_addr: Option<SocketAddr>,
+        _error: &(dyn Error + Send + Sync),
+    ) {
+    }
+
+    /// Called when accepting a new connection fails. The listener stops accepting
+    /// connections, and the error is also returned by `Server::recv()`.
+    fn accept_failed(&self, _listener_index: usize, _error: &IoError) {}
+
+    /// Called when a request sent by a client can't be read.
+    fn request_error(&self, _connection: &ConnectionInfo, _error: &RequestError) {}
+
+    /// Called when the server answers on its own with an error: after a `request_error()`,
+    /// or with a `500 Internal Server Error` when a `Request` is dropped without a response,
+    /// or with a `503 Service Unavailable` when the `Server` is dropped with queued requests.
+    fn error_response(&self, _connection: &ConnectionInfo, _status: StatusCode) {}
+
+    /// Called when a `Request` is dropped without a response, before the response chosen by
+    /// the `UnansweredRequestPolicy` is sent. This includes the requests still queued when
+    /// the `Server` is dropped.
+    fn request_unanswered(&self, _request: &Request) {}
+
+    /// Called when a request has been read, right before it is handed to the application.
+    fn request_dispatched(&self, _request: &Request) {}
+
+    /// Called when the response to a request has been written to the client.
+    ///
+    /// This is not called for requests turned into a writer or upgraded, since the server
+    /// doesn't know when the application is done with them.
+    fn response_completed(&self, _response: &ResponseInfo<'_>) {}
+}
+
+impl fmt::Debug for dyn ServerHooks {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("ServerHooks")
+    }
+}
+
+/// Description of a client connection, passed to `ServerHooks`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+    /// Identifier of the connection, unique for the lifetime of the server.
+    pub id: usize,
+
+    /// Index of the listener that accepted the connection.
+    pub listener_index: usize,
+
+    /// Address of the client, for TCP connections.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// Address the connection was accepted on.
+    pub local_addr: Option<ListenAddr>,
+
+    /// `true` if the connection uses SSL.
+    pub secure: bool,
+
+    /// What was negotiated during the SSL handshake, if the connection uses SSL.
+    pub tls: Option<TlsInfo>,
+}
+
+/// Description of a response written to a client, passed to
+/// `ServerHooks::response_completed()`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ResponseInfo<'a> {
+    /// The connection the response was sent on.
+    pub connection: &'a ConnectionInfo,
+
+    /// Method of the request.
+    pub method: &'a Method,
+
+    /// URL of the request.
+    pub url: &'a Uri,
+
+    /// Status code of the response.
+    pub status: StatusCode,
+
+    /// The error that prevented the response from being written completely, if any.
+    pub error: Option<&'a IoError>,
+}
+
+/// Reason why a request sent by a client could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RequestError {
+    /// The request line is not `<method> <target> HTTP/<version>`.
+    MalformedRequestLine,
+
+    /// A header line is not a valid `<name>: <value>` pair.
+    MalformedHeader,
+
+    /// The client sent an `Expect` header that the server doesn't support.
+    UnsupportedExpectation,
+
+    /// The request uses an HTTP version that the server doesn't support.
+    UnsupportedVersion(Version),
+
+    /// The client didn't send the request in time.
+    Timeout,
+
+    /// The body of the request uses a `Content-Encoding` that the server can't decompress.
+    UnsupportedContentEncoding,
+}
+
+impl RequestError {
+    /// Status code of the response sent for this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            RequestError::MalformedRequestLine | RequestError::MalformedHeader => {
+                StatusCode::BAD_REQUEST
+            }
+            RequestError::UnsupportedExpectation => StatusCode::EXPECTATION_FAILED,
+            RequestError::UnsupportedVersion(_) => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            RequestError::Timeout => StatusCode::REQUEST_TIMEOUT,
+            RequestError::UnsupportedContentEncoding => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::MalformedRequestLine => formatter.write_str("malformed request line"),
+            RequestError::MalformedHeader => formatter.write_str("malformed header"),
+            RequestError::UnsupportedExpectation => {
+                formatter.write_str("unsupported Expect header")
+            }
+            RequestError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported HTTP version {:?}", version)
+            }
+            RequestError::Timeout => formatter.write_str("timed out reading the request"),
+            RequestError::UnsupportedContentEncoding => {
+                formatter.write_str("unsupported Content-Encoding")
+            }
+        }
+    }
+}
+
+impl Error for RequestError {}
@@ -60,6 +60,19 @@
 //! }
 //! ```
 //!
+//! `server.serve()` does the same, and also keeps the workers alive when a handler panics:
+//!
+//! ```no_run
+//! # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+//! server.serve(
+//!     |rq| {
+//!         // ...
+//!         # let _ = rq;
+//!     },
+//!     4,
+//! );
+//! ```
+//!
 //! If you don't want to block, you can call `server.try_recv()` instead.
 //!
 //! ## Handling requests
@@ -87,7 +100,8 @@
 //! # let response = tiny_http::Response::from_file(File::open(&Path::new("image.png")).unwrap());
 //! let _ = request.respond(response);
 //! ```
-#![forbid(unsafe_code)]
+// `unsafe` is only allowed in `util::fd`, to take ownership of raw file descriptors
+#![deny(unsafe_code)]
 #![deny(rust_2018_idioms)]
 #![allow(clippy::match_like_matches_macro)]
 
@@ -102,11 +116,14 @@ use std::error::Error;
 use std::io::Error as IoError;
 use std::io::ErrorKind as IoErrorKind;
 use std::io::Result as IoResult;
-use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::net::ToSocketAddrs;
+#[cfg(not(unix))]
+use std::net::{Shutdown, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
@@ -114,25 +131,56 @@ use client::ClientConnection;
 use connection::Connection;
 use util::MessagesQueue;
 
-pub use connection::{ConfigListenAddr, ListenAddr, Listener};
-pub use request::{ReadWrite, Request};
+#[cfg(feature = "async")]
+pub use asynchronous::{IncomingAsync, RecvAsync, RespondAsync};
+#[cfg(feature = "compression")]
+pub use compression::{CompressionConfig, ContentEncoding, DecompressionConfig};
+#[cfg(unix)]
+pub use connection::UnixSocketOptions;
+pub use connection::{ConfigListenAddr, ListenAddr, Listener, PeerCredentials, SocketOptions};
+pub use error_response::{DefaultErrorResponses, ErrorContext, ErrorResponseFactory};
+pub use extensions::{ArrivalTime, ConnectionId, TlsInfo};
+pub use hooks::{ConnectionInfo, RequestError, ResponseInfo, ServerHooks};
+pub use request::{
+    ReadWrite, Request, RequestBody, RequestHead, Responder, UnansweredRequestPolicy,
+};
 pub use response::{Response, ResponseBox};
+pub use static_files::StaticFiles;
+pub use streaming::StreamingWriter;
 pub use test::TestRequest;
 
+#[cfg(feature = "async")]
+mod asynchronous;
 mod client;
+#[cfg(feature = "compression")]
+mod compression;
+mod conditional;
 mod connection;
+mod error_response;
+mod extensions;
+#[cfg(target_os = "linux")]
+mod handoff;
+mod hooks;
 mod log;
+mod range;
 mod request;
 mod response;
+pub mod sse;
 mod ssl;
+mod static_files;
+mod streaming;
+#[cfg(target_os = "linux")]
+mod systemd;
 mod test;
 mod util;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 pub use http;
 
 /// The main class of this library.
 ///
-/// Destroying this object will immediately close the listening socket and the reading
+/// Destroying this object will immediately close the listening sockets and the reading
 ///  part of all the client's connections. Requests that have already been returned by
 ///  the `recv()` function will not close and the responses will be transferred to the client.
 pub struct Server {
@@ -143,13 +191,29 @@ pub struct Server {
     // queue for messages received by child threads
     messages: Arc<MessagesQueue<Message>>,
 
-    // result of TcpListener::local_addr()
-    listening_addr: ListenAddr,
+    // result of Listener::local_addr() for every listener, in configuration order
+    listening_addrs: Vec<ListenAddr>,
+
+    // handles to the listening sockets, to hand them over to another process
+    #[cfg(target_os = "linux")]
+    listeners: Vec<Listener>,
+
+    // dropping this socket wakes up the accept threads, which are waiting for it to be readable
+    #[cfg(unix)]
+    accept_interrupt: Mutex<Option<std::os::unix::net::UnixStream>>,
+
+    // client connections that are open
+    connections: Arc<util::Connections>,
+
+    // socket files to remove when the server is destroyed
+    #[cfg(unix)]
+    socket_files: Mutex<Vec<connection::SocketFile>>,
 }
 
 enum Message {
     Error(IoError),
-    NewRequest(Request),
+    // boxed, since a request is much larger than an error
+    NewRequest(Box<Request>),
 }
 
 impl From<IoError> for Message {
@@ -160,28 +224,126 @@ impl From<IoError> for Message {
 
 impl From<Request> for Message {
     fn from(rq: Request) -> Message {
-        Message::NewRequest(rq)
+        Message::NewRequest(Box::new(rq.with_queued(true)))
     }
 }
 
-// this trait is to make sure that Server implements Share and Send
-#[doc(hidden)]
-trait MustBeShareDummy: Sync + Send {}
-#[doc(hidden)]
-impl MustBeShareDummy for Server {}
+// makes sure that Server implements Sync and Send
+const _: () = {
+    const fn assert_sync_send<T: Sync + Send>() {}
+    assert_sync_send::<Server>();
+};
+
+// what an accept thread shares with the server
+struct AcceptContext {
+    listener_index: usize,
+    close: Arc<AtomicBool>,
+    #[cfg(unix)]
+    interrupted: Arc<std::os::unix::net::UnixStream>,
+    messages: Arc<MessagesQueue<Message>>,
+    connections: Arc<util::Connections>,
+    settings: Arc<ConnectionSettings>,
+}
+
+// what the threads of the pool need to serve a new client
+struct ClientContext {
+    listener_index: usize,
+    messages: Arc<MessagesQueue<Message>>,
+    connections: Arc<util::Connections>,
+    settings: Arc<ConnectionSettings>,
+}
+
+// settings of the server that apply to every connection
+pub(crate) struct ConnectionSettings {
+    socket_options: SocketOptions,
+    hooks: Option<Arc<dyn ServerHooks>>,
+    error_responses: Option<Arc<dyn ErrorResponseFactory>>,
+    unanswered_requests: UnansweredRequestPolicy,
+    #[cfg(feature = "compression")]
+    compression: Option<CompressionConfig>,
+    #[cfg(feature = "compression")]
+    request_decompression: Option<DecompressionConfig>,
+}
 
 pub struct IncomingRequests<'a> {
     server: &'a Server,
 }
 
 /// Represents the parameters required to create a server.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ServerConfig {
+    /// The sockets to listen on. Every entry is bound, and requests from all of them are
+    /// delivered by the same `Server`.
+    pub listeners: Vec<ListenerConfig>,
+
+    /// Options applied to every listening socket and to the connections they accept.
+    pub socket_options: SocketOptions,
+
+    /// Callbacks notified of the events of the server.
+    pub hooks: Option<Arc<dyn ServerHooks>>,
+
+    /// Builds the responses sent when a request can't be read. If `None`, the responses of
+    /// `DefaultErrorResponses` are sent.
+    pub error_responses: Option<Arc<dyn ErrorResponseFactory>>,
+
+    /// What to do with the requests dropped without a response.
+    pub unanswered_requests: UnansweredRequestPolicy,
+
+    /// If `Some`, the responses given to `Request::respond()` are compressed when the client
+    /// accepts it. Responses sent with `respond_streaming()` or `into_writer()` are not.
+    #[cfg(feature = "compression")]
+    pub compression: Option<CompressionConfig>,
+
+    /// If `Some`, the request bodies with a `Content-Encoding` are decompressed when they are
+    /// read, and the requests with an unsupported encoding are rejected.
+    #[cfg(feature = "compression")]
+    pub request_decompression: Option<DecompressionConfig>,
+}
+
+/// Represents the parameters of a single listening socket.
+#[derive(Debug, Clone)]
+pub struct ListenerConfig {
     /// The addresses to try to listen to.
     pub addr: ConfigListenAddr,
 
-    /// If `Some`, then the server will use SSL to encode the communications.
+    /// If `Some`, then the connections accepted by this listener will use SSL to encode
+    /// the communications.
     pub ssl: Option<SslConfig>,
+
+    /// Lifecycle of the socket file, when listening on a UNIX socket path.
+    #[cfg(unix)]
+    pub unix_socket: UnixSocketOptions,
+}
+
+impl ListenerConfig {
+    /// Builds a plain HTTP listener configuration for the given address.
+    pub fn new(addr: ConfigListenAddr) -> ListenerConfig {
+        ListenerConfig {
+            addr,
+            ssl: None,
+            #[cfg(unix)]
+            unix_socket: UnixSocketOptions::default(),
+        }
+    }
+
+    /// Returns the same configuration, but with SSL enabled.
+    pub fn with_ssl(mut self, config: SslConfig) -> ListenerConfig {
+        self.ssl = Some(config);
+        self
+    }
+
+    /// Returns the same configuration, but with different UNIX socket options.
+    #[cfg(unix)]
+    pub fn with_unix_socket_options(mut self, options: UnixSocketOptions) -> ListenerConfig {
+        self.unix_socket = options;
+        self
+    }
+}
+
+impl From<ConfigListenAddr> for ListenerConfig {
+    fn from(addr: ConfigListenAddr) -> ListenerConfig {
+        ListenerConfig::new(addr)
+    }
 }
 
 /// Configuration of the server for SSL.
@@ -193,6 +355,28 @@ pub struct SslConfig {
     pub private_key: Vec<u8>,
 }
 
+// building the SSL capabilities
+#[cfg(any(
+    all(feature = "ssl-openssl", feature = "ssl-rustls"),
+    all(feature = "ssl-openssl", feature = "ssl-native-tls"),
+    all(feature = "ssl-native-tls", feature = "ssl-rustls"),
+))]
+compile_error!(
+    "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
+);
+#[cfg(not(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+)))]
+type SslContext = ();
+#[cfg(any(
+    feature = "ssl-openssl",
+    feature = "ssl-rustls",
+    feature = "ssl-native-tls"
+))]
+type SslContext = crate::ssl::SslContextImpl;
+
 impl Server {
     /// Shortcut for a simple server on a specific address.
     #[inline]
@@ -201,8 +385,10 @@ impl Server {
         A: ToSocketAddrs,
     {
         Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
-            ssl: None,
+            listeners: vec![ListenerConfig::new(ConfigListenAddr::from_socket_addrs(
+                addr,
+            )?)],
+            ..ServerConfig::default()
         })
     }
 
@@ -221,8 +407,10 @@ impl Server {
         A: ToSocketAddrs,
     {
         Server::new(ServerConfig {
-            addr: ConfigListenAddr::from_socket_addrs(addr)?,
-            ssl: Some(config),
+            listeners: vec![
+                ListenerConfig::new(ConfigListenAddr::from_socket_addrs(addr)?).with_ssl(config),
+            ],
+            ..ServerConfig::default()
         })
     }
 
@@ -233,15 +421,15 @@ impl Server {
         path: &std::path::Path,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
         Server::new(ServerConfig {
-            addr: ConfigListenAddr::unix_from_path(path),
-            ssl: None,
+            listeners: vec![ListenerConfig::new(ConfigListenAddr::unix_from_path(path))],
+            ..ServerConfig::default()
         })
     }
 
-    /// Builds a new server that listens on the specified address.
-    pub fn new(config: ServerConfig) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        let listener = config.addr.bind()?;
-        Self::from_listener(listener, config.ssl)
+    /// Builds a new server that listens on all the specified addresses.
+    pub fn new(mut config: ServerConfig) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
+        let listeners = Self::bind_listeners(&mut config)?;
+        Self::from_bound_listeners(listeners, config)
     }
 
     /// Builds a new server using the specified TCP listener.
@@ -252,145 +440,396 @@ impl Server {
         listener: L,
         ssl_config: Option<SslConfig>,
     ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>> {
-        let listener = listener.into();
+        Self::from_listeners(std::iter::once((listener, ssl_config)))
+    }
+
+    /// Builds a new server from the sockets passed by systemd socket activation.
+    ///
+    /// `ssl_config` is called with the name of every socket (`FileDescriptorName=` in the socket
+    /// unit, or `"unknown"`), so that SSL can be enabled on some of them only. Unlike
+    /// `from_listeners()`, the socket files of UNIX listeners are left in place when the server is
+    /// dropped, since they belong to systemd.
+    ///
+    /// See `Listener::from_systemd()` for more details.
+    #[cfg(target_os = "linux")]
+    pub fn from_systemd<F>(ssl_config: F) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
+    where
+        F: FnMut(&str) -> Option<SslConfig>,
+    {
+        Self::from_systemd_with_config(ssl_config, ServerConfig::default())
+    }
+
+    /// Same as `from_systemd()`, with the other settings of the server taken from `config`.
+    ///
+    /// The listeners of `config` are bound as well, and come after the sockets passed by systemd.
+    #[cfg(target_os = "linux")]
+    pub fn from_systemd_with_config<F>(
+        mut ssl_config: F,
+        mut config: ServerConfig,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
+    where
+        F: FnMut(&str) -> Option<SslConfig>,
+    {
+        // bound first, so that a failure doesn't close the sockets passed by systemd
+        let bound = Self::bind_listeners(&mut config)?;
+
+        let listeners = Listener::from_systemd()?;
+        if listeners.is_empty() {
+            return Err(
+                "The process was not started by systemd socket activation, or its sockets \
+                 were already claimed"
+                    .into(),
+            );
+        }
+
+        Self::from_bound_listeners(
+            listeners
+                .into_iter()
+                .map(|(name, listener)| (listener, ssl_config(&name), false))
+                .chain(bound),
+            config,
+        )
+    }
+
+    /// Builds a new server using several already bound listeners.
+    ///
+    /// Each listener comes with its own optional SSL configuration, so that plain and
+    /// encrypted sockets (TCP or UNIX) can be served by the same `Server`. The order of
+    /// the listeners is the one reported by `server_addrs()` and `Request::listener_index()`.
+    ///
+    /// The socket files of UNIX listeners are removed when the server is destroyed.
+    pub fn from_listeners<I, L>(
+        listeners: I,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
+    where
+        I: IntoIterator<Item = (L, Option<SslConfig>)>,
+        L: Into<Listener>,
+    {
+        Self::from_listeners_with_config(listeners, ServerConfig::default())
+    }
+
+    /// Same as `from_listeners()`, with the other settings of the server taken from `config`.
+    ///
+    /// The listeners of `config` are bound as well, and come after the given ones.
+    pub fn from_listeners_with_config<I, L>(
+        listeners: I,
+        mut config: ServerConfig,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
+    where
+        I: IntoIterator<Item = (L, Option<SslConfig>)>,
+        L: Into<Listener>,
+    {
+        let bound = Self::bind_listeners(&mut config)?;
+        Self::from_bound_listeners(
+            listeners
+                .into_iter()
+                .map(|(listener, ssl_config)| (listener.into(), ssl_config, true))
+                .chain(bound),
+            config,
+        )
+    }
+
+    // binds the listeners of `config`, which are taken out of it
+    fn bind_listeners(
+        config: &mut ServerConfig,
+    ) -> IoResult<Vec<(Listener, Option<SslConfig>, bool)>> {
+        let mut listeners = Vec::with_capacity(config.listeners.len());
+        for listener_config in std::mem::take(&mut config.listeners) {
+            #[cfg(unix)]
+            let (listener, remove_on_drop) = (
+                listener_config
+                    .addr
+                    .bind_with_options(&config.socket_options, &listener_config.unix_socket)?,
+                listener_config.unix_socket.remove_on_drop,
+            );
+            #[cfg(not(unix))]
+            let (listener, remove_on_drop) =
+                (listener_config.addr.bind(&config.socket_options)?, false);
+            listeners.push((listener, listener_config.ssl, remove_on_drop));
+        }
+        Ok(listeners)
+    }
+
+    // the listeners of `config` must have been bound already
+    fn from_bound_listeners<I>(
+        listeners: I,
+        config: ServerConfig,
+    ) -> Result<Server, Box<dyn Error + Send + Sync + 'static>>
+    where
+        I: IntoIterator<Item = (Listener, Option<SslConfig>, bool)>,
+    {
+        debug_assert!(config.listeners.is_empty());
+        let settings = ConnectionSettings {
+            socket_options: config.socket_options,
+            hooks: config.hooks,
+            error_responses: config.error_responses,
+            unanswered_requests: config.unanswered_requests,
+            #[cfg(feature = "compression")]
+            compression: config.compression,
+            #[cfg(feature = "compression")]
+            request_decompression: config.request_decompression,
+        };
+
         // building the "close" variable
         let close_trigger = Arc::new(AtomicBool::new(false));
 
-        // building the TcpListener
-        let (server, local_addr) = {
+        #[cfg(unix)]
+        let mut socket_files = Vec::new();
+
+        // building the listeners and their SSL capabilities before spawning anything, so that
+        // a configuration error doesn't leave accept threads behind
+        let mut prepared = Vec::new();
+        for (listener, ssl_config, remove_on_drop) in listeners {
             let local_addr = listener.local_addr()?;
-            log::debug!("Server listening on {}", local_addr);
-            (listener, local_addr)
-        };
 
-        // building the SSL capabilities
-        #[cfg(any(
-            all(feature = "ssl-openssl", feature = "ssl-rustls"),
-            all(feature = "ssl-openssl", feature = "ssl-native-tls"),
-            all(feature = "ssl-native-tls", feature = "ssl-rustls"),
-        ))]
-        compile_error!(
-            "Only one feature from 'ssl-openssl', 'ssl-rustls', 'ssl-native-tls' can be enabled at the same time"
-        );
-        #[cfg(not(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        )))]
-        type SslContext = ();
-        #[cfg(any(
-            feature = "ssl-openssl",
-            feature = "ssl-rustls",
-            feature = "ssl-native-tls"
-        ))]
-        type SslContext = crate::ssl::SslContextImpl;
-        let ssl: Option<SslContext> = {
-            match ssl_config {
-                #[cfg(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                ))]
-                Some(config) => Some(SslContext::from_pem(
-                    config.certificate,
-                    Zeroizing::new(config.private_key),
-                )?),
-                #[cfg(not(any(
-                    feature = "ssl-openssl",
-                    feature = "ssl-rustls",
-                    feature = "ssl-native-tls"
-                )))]
-                Some(_) => return Err(
-                    "Building a server with SSL requires enabling the `ssl` feature in tiny-http"
-                        .into(),
-                ),
-                None => None,
+            #[cfg(unix)]
+            if remove_on_drop {
+                socket_files.extend(connection::SocketFile::of(&listener));
             }
-        };
+            #[cfg(not(unix))]
+            let _ = remove_on_drop;
+            log::debug!("Server listening on {}", local_addr);
+
+            let ssl: Option<SslContext> = {
+                match ssl_config {
+                    #[cfg(any(
+                        feature = "ssl-openssl",
+                        feature = "ssl-rustls",
+                        feature = "ssl-native-tls"
+                    ))]
+                    Some(config) => Some(SslContext::from_pem(
+                        config.certificate,
+                        Zeroizing::new(config.private_key),
+                    )?),
+                    #[cfg(not(any(
+                        feature = "ssl-openssl",
+                        feature = "ssl-rustls",
+                        feature = "ssl-native-tls"
+                    )))]
+                    Some(_) => return Err(
+                        "Building a server with SSL requires enabling the `ssl` feature in tiny-http"
+                            .into(),
+                    ),
+                    None => None,
+                }
+            };
 
-        // creating a task where server.accept() is continuously called
+            prepared.push((listener, local_addr, ssl));
+        }
+
+        if prepared.is_empty() {
+            return Err("A server requires at least one listener".into());
+        }
+
+        // creating one task per listener where server.accept() is continuously called
         // and ClientConnection objects are pushed in the messages queue
         let messages = MessagesQueue::with_capacity(8);
 
-        let inside_close_trigger = close_trigger.clone();
-        let inside_messages = messages.clone();
-        thread::spawn(move || {
-            // a tasks pool is used to dispatch the connections into threads
-            let tasks_pool = util::TaskPool::new();
+        // a tasks pool is used to dispatch the connections into threads
+        // the queue is woken once the server has drained; the requests it holds keep the
+        // connections alive, so a strong reference to it would leak
+        let connections = {
+            let messages = Arc::downgrade(&messages);
+            util::Connections::new(util::TaskPool::new(), move || {
+                if let Some(messages) = messages.upgrade() {
+                    messages.wake_all();
+                }
+            })
+        };
+        let settings = Arc::new(settings);
 
-            log::debug!("Running accept thread");
-            while !inside_close_trigger.load(Relaxed) {
-                let new_client = match server.accept() {
-                    Ok((sock, _)) => {
-                        use util::RefinedTcpStream;
-                        let (read_closable, write_closable) = match ssl {
-                            None => RefinedTcpStream::new(sock),
-                            #[cfg(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            ))]
-                            Some(ref ssl) => {
-                                // trying to apply SSL over the connection
-                                // if an error occurs, we just close the socket and resume listening
-                                let sock = match ssl.accept(sock) {
-                                    Ok(s) => s,
-                                    Err(_) => continue,
-                                };
-
-                                RefinedTcpStream::new(sock)
-                            }
-                            #[cfg(not(any(
-                                feature = "ssl-openssl",
-                                feature = "ssl-rustls",
-                                feature = "ssl-native-tls"
-                            )))]
-                            Some(ref _ssl) => unreachable!(),
-                        };
+        #[cfg(unix)]
+        let (accept_interrupt, accept_interrupted) = {
+            let (sender, receiver) = std::os::unix::net::UnixStream::pair()?;
+            (sender, Arc::new(receiver))
+        };
 
-                        Ok(ClientConnection::new(write_closable, read_closable))
-                    }
-                    Err(e) => Err(e),
-                };
+        let mut listening_addrs = Vec::with_capacity(prepared.len());
+        #[cfg(target_os = "linux")]
+        let mut listeners = Vec::with_capacity(prepared.len());
+        for (index, (listener, local_addr, ssl)) in prepared.into_iter().enumerate() {
+            listening_addrs.push(local_addr);
+            #[cfg(target_os = "linux")]
+            listeners.push(listener.try_clone()?);
+            Self::spawn_accept_thread(
+                listener,
+                ssl,
+                AcceptContext {
+                    listener_index: index,
+                    close: close_trigger.clone(),
+                    #[cfg(unix)]
+                    interrupted: accept_interrupted.clone(),
+                    messages: messages.clone(),
+                    connections: connections.clone(),
+                    settings: settings.clone(),
+                },
+            );
+        }
 
-                match new_client {
-                    Ok(client) => {
-                        let messages = inside_messages.clone();
-                        let mut client = Some(client);
-                        tasks_pool.spawn(Box::new(move || {
-                            if let Some(client) = client.take() {
-                                // Synchronization is needed for HTTPS requests to avoid a deadlock
-                                if client.secure() {
-                                    let (sender, receiver) = mpsc::channel();
-                                    for rq in client {
-                                        messages.push(rq.with_notify_sender(sender.clone()).into());
-                                        receiver.recv().unwrap();
-                                    }
-                                } else {
-                                    for rq in client {
-                                        messages.push(rq.into());
-                                    }
-                                }
-                            }
-                        }));
+        // result
+        Ok(Server {
+            messages,
+            close: close_trigger,
+            listening_addrs,
+            #[cfg(target_os = "linux")]
+            listeners,
+            #[cfg(unix)]
+            accept_interrupt: Mutex::new(Some(accept_interrupt)),
+            connections,
+            #[cfg(unix)]
+            socket_files: Mutex::new(socket_files),
+        })
+    }
+
+    fn spawn_accept_thread(server: Listener, ssl: Option<SslContext>, context: AcceptContext) {
+        let AcceptContext {
+            listener_index,
+            close: inside_close_trigger,
+            #[cfg(unix)]
+            interrupted,
+            messages: inside_messages,
+            connections,
+            settings,
+        } = context;
+
+        let ssl = ssl.map(Arc::new);
+        thread::spawn(move || {
+            log::debug!("Running accept thread for listener #{}", listener_index);
+            while !inside_close_trigger.load(Relaxed) {
+                #[cfg(unix)]
+                {
+                    use std::os::fd::AsFd;
+
+                    match server.wait_for_client(interrupted.as_fd()) {
+                        Ok(true) => (),
+                        Ok(false) => break,
+                        Err(e) => {
+                            log::error!("Error waiting for new clients: {}", e);
+                            inside_messages.push(e.into());
+                            break;
+                        }
                     }
+                }
 
+                let (sock, remote_addr) = match server.accept() {
+                    Ok(client) => client,
                     Err(e) => {
                         log::error!("Error accepting new client: {}", e);
+                        if let Some(ref hooks) = settings.hooks {
+                            hooks.accept_failed(listener_index, &e);
+                        }
                         inside_messages.push(e.into());
                         break;
                     }
+                };
+
+                if let Err(e) = sock.apply_options(&settings.socket_options) {
+                    log::error!("Error setting options of new client socket: {}", e);
                 }
+
+                // the SSL handshake is done by the threads of the pool too, so that a slow
+                // client can't keep this thread from accepting the other ones
+                let ssl = ssl.clone();
+                let context = ClientContext {
+                    listener_index,
+                    messages: inside_messages.clone(),
+                    connections: connections.clone(),
+                    settings: settings.clone(),
+                };
+                let mut sock = Some(sock);
+                connections.spawn(Box::new(move || {
+                    if let Some(sock) = sock.take() {
+                        Self::serve_client(sock, remote_addr, ssl.as_deref(), &context);
+                    }
+                }));
             }
-            log::debug!("Terminating accept thread");
+            log::debug!("Terminating accept thread for listener #{}", listener_index);
         });
+    }
 
-        // result
-        Ok(Server {
-            messages,
-            close: close_trigger,
-            listening_addr: local_addr,
-        })
+    /// Completes the SSL handshake with a new client, if any, and then dispatches its requests.
+    fn serve_client(
+        sock: Connection,
+        remote_addr: Option<std::net::SocketAddr>,
+        ssl: Option<&SslContext>,
+        context: &ClientContext,
+    ) {
+        use util::RefinedTcpStream;
+
+        let ClientContext {
+            listener_index,
+            ref messages,
+            ref connections,
+            ref settings,
+        } = *context;
+
+        // kept to close the connection if the server stops while it is idle
+        let closer = sock.try_clone().ok();
+        let local_addr = sock.local_addr().ok();
+
+        let ((read_closable, write_closable), tls) = match ssl {
+            None => (RefinedTcpStream::new(sock), None),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Some(ssl) => {
+                // trying to apply SSL over the connection
+                // if an error occurs, we just close the socket
+                let sock = match ssl.accept(sock) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        if let Some(ref hooks) = settings.hooks {
+                            hooks.tls_handshake_failed(listener_index, remote_addr, &*e);
+                        }
+                        return;
+                    }
+                };
+
+                let tls = sock.tls_info();
+                (RefinedTcpStream::new(sock), Some(tls))
+            }
+            #[cfg(not(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            )))]
+            Some(_) => unreachable!(),
+        };
+
+        let info = ConnectionInfo {
+            id: 0,
+            listener_index,
+            remote_addr,
+            local_addr,
+            secure: tls.is_some(),
+            tls,
+        };
+        let client = ClientConnection::new(write_closable, read_closable)
+            .with_guard(connections.register(closer, info, settings.clone()));
+
+        let dispatch = |rq: Request| {
+            if let Some(ref hooks) = settings.hooks {
+                hooks.request_dispatched(&rq);
+            }
+            messages.push(rq.into());
+        };
+        // Synchronization is needed for HTTPS requests to avoid a deadlock
+        if client.secure() {
+            let (sender, receiver) = mpsc::channel();
+            for rq in client {
+                let rq = rq
+                    .with_listener_index(listener_index)
+                    .with_notify_sender(sender.clone());
+                dispatch(rq);
+                receiver.recv().unwrap();
+            }
+        } else {
+            for rq in client {
+                dispatch(rq.with_listener_index(listener_index));
+            }
+        }
     }
 
     /// Returns an iterator for all the incoming requests.
@@ -402,22 +841,68 @@ impl Server {
     }
 
     /// Returns the address the server is listening to.
+    ///
+    /// If the server has several listeners, this is the address of the first one.
+    /// See `server_addrs()` for the complete list.
     #[inline]
     pub fn server_addr(&self) -> ListenAddr {
-        self.listening_addr.clone()
+        self.listening_addrs[0].clone()
+    }
+
+    /// Returns the addresses of all the listeners of the server.
+    ///
+    /// The addresses are in configuration order, so that the position of an address in this
+    /// list matches `Request::listener_index()`.
+    #[inline]
+    pub fn server_addrs(&self) -> &[ListenAddr] {
+        &self.listening_addrs
     }
 
     /// Returns the number of clients currently connected to the server.
+    ///
+    /// A connection is counted until the client closes it, or until its last request has been
+    /// answered (or turned into a writer) once the server stopped reading from it.
     pub fn num_connections(&self) -> usize {
-        unimplemented!()
-        //self.requests_receiver.lock().len()
+        self.connections.len()
+    }
+
+    /// Stops accepting new connections, to shut down gracefully.
+    ///
+    /// The listening sockets stay open until the server is dropped, so that clients connecting
+    /// in the meantime wait instead of being refused (for instance until another process that
+    /// received them through `send_listeners()` accepts them).
+    ///
+    /// No new request is read from the established connections: the ones waiting for a request
+    /// are closed right away, and the other ones after their current request. The requests that
+    /// have already been read can still be received and answered; the server has drained when
+    /// `num_connections()` reaches zero.
+    pub fn stop_accepting(&self) {
+        if self.close.swap(true, Relaxed) {
+            return;
+        }
+
+        #[cfg(unix)]
+        drop(self.accept_interrupt.lock().unwrap().take());
+
+        #[cfg(not(unix))]
+        for listening_addr in &self.listening_addrs {
+            // Connect briefly to ourselves to unblock the accept thread
+            let maybe_stream = match listening_addr {
+                ListenAddr::IP(addr) => TcpStream::connect(addr).map(Connection::from),
+            };
+            if let Ok(stream) = maybe_stream {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+
+        self.connections.stop();
     }
 
     /// Blocks until an HTTP request has been submitted and returns it.
     pub fn recv(&self) -> IoResult<Request> {
         match self.messages.pop() {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(rq),
+            Some(Message::NewRequest(rq)) => Ok(rq.with_queued(false)),
             None => Err(IoError::new(IoErrorKind::Other, "thread unblocked")),
         }
     }
@@ -426,7 +911,7 @@ impl Server {
     pub fn recv_timeout(&self, timeout: Duration) -> IoResult<Option<Request>> {
         match self.messages.pop_timeout(timeout) {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(Some(rq)),
+            Some(Message::NewRequest(rq)) => Ok(Some(rq.with_queued(false))),
             None => Ok(None),
         }
     }
@@ -435,7 +920,7 @@ impl Server {
     pub fn try_recv(&self) -> IoResult<Option<Request>> {
         match self.messages.try_pop() {
             Some(Message::Error(err)) => Err(err),
-            Some(Message::NewRequest(rq)) => Ok(Some(rq)),
+            Some(Message::NewRequest(rq)) => Ok(Some(rq.with_queued(false))),
             None => Ok(None),
         }
     }
@@ -446,6 +931,65 @@ impl Server {
     pub fn unblock(&self) {
         self.messages.unblock();
     }
+
+    /// Answers the incoming requests with `handler`, on `workers` threads.
+    ///
+    /// If `handler` panics, the worker thread survives and the request is dropped, so the client
+    /// gets the response of the `UnansweredRequestPolicy` (a `500 Internal Server Error` by
+    /// default).
+    ///
+    /// Returns when `unblock()` is called, after the requests being handled are finished, or
+    /// once the server has drained after `stop_accepting()`: every request already read is
+    /// handled before returning. Errors returned by `recv()` are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let server = tiny_http::Server::http("0.0.0.0:8000").unwrap();
+    ///
+    /// server.serve(
+    ///     |request| {
+    ///         let _ = request.respond(tiny_http::Response::from_string("hello world"));
+    ///     },
+    ///     4,
+    /// );
+    /// ```
+    pub fn serve<F>(&self, handler: F, workers: usize)
+    where
+        F: Fn(Request) + Send + Sync,
+    {
+        assert!(workers > 0, "a server needs at least one worker");
+
+        let unblocked = AtomicBool::new(false);
+        let stop = || unblocked.load(Relaxed) || self.connections.is_drained();
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while !unblocked.load(Relaxed) {
+                        let request = match self.messages.pop_unless(stop) {
+                            Some(Message::NewRequest(rq)) => rq.with_queued(false),
+                            Some(Message::Error(_)) => continue,
+                            None => {
+                                // tell the other workers to stop too
+                                unblocked.store(true, Relaxed);
+                                self.messages.wake_all();
+                                break;
+                            }
+                        };
+
+                        if panic::catch_unwind(AssertUnwindSafe(|| handler(request))).is_err() {
+                            log::error!("Request handler panicked");
+                        }
+                    }
+                });
+            }
+        });
+    }
 }
 
 impl Iterator for IncomingRequests<'_> {
@@ -457,26 +1001,11 @@ impl Iterator for IncomingRequests<'_> {
 
 impl Drop for Server {
     fn drop(&mut self) {
-        self.close.store(true, Relaxed);
-        // Connect briefly to ourselves to unblock the accept thread
-        let maybe_stream = match &self.listening_addr {
-            ListenAddr::IP(addr) => TcpStream::connect(addr).map(Connection::from),
-            #[cfg(unix)]
-            ListenAddr::Unix(addr) => {
-                // TODO: use connect_addr when its stabilized.
-                let path = addr.as_pathname().unwrap();
-                std::os::unix::net::UnixStream::connect(path).map(Connection::from)
-            }
-        };
-        if let Ok(stream) = maybe_stream {
-            let _ = stream.shutdown(Shutdown::Both);
-        }
+        self.stop_accepting();
 
         #[cfg(unix)]
-        if let ListenAddr::Unix(addr) = &self.listening_addr {
-            if let Some(path) = addr.as_pathname() {
-                let _ = std::fs::remove_file(path);
-            }
+        for socket_file in self.socket_files.lock().unwrap().iter() {
+            socket_file.remove();
         }
     }
 }
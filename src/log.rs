@@ -4,13 +4,23 @@ pub(crate) use log::{debug, error};
 #[cfg(not(feature = "log"))]
 macro_rules! _debug {
     (target: $target:expr, $($arg:tt)+) => {};
-    ($($arg:tt)+) => {};
+    ($($arg:tt)+) => {
+        // keeps the arguments used, without formatting anything
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
 }
 
 #[cfg(not(feature = "log"))]
 macro_rules! _error {
     (target: $target:expr, $($arg:tt)+) => {};
-    ($($arg:tt)+) => {};
+    ($($arg:tt)+) => {
+        // keeps the arguments used, without formatting anything
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
 }
 
 #[cfg(not(feature = "log"))]
@@ -0,0 +1,300 @@
+//! Range requests (RFC 9110, section 14) for the responses built from files.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Cursor, Read, Result as IoResult, Seek, SeekFrom};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use httpdate::HttpDate;
+
+use crate::Response;
+
+/// Requests with more ranges than this get the whole file, since many small ranges cost more
+/// than the file itself.
+const MAX_RANGES: usize = 64;
+
+/// Body of a response, whole or made of ranges of a file.
+pub(crate) enum RangeBody<R> {
+    Whole(R),
+    Ranges(Ranges),
+}
+
+impl<R: Read> Read for RangeBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            RangeBody::Whole(reader) => reader.read(buf),
+            RangeBody::Ranges(ranges) => ranges.read(buf),
+        }
+    }
+}
+
+/// Reads the parts of a `206 Partial Content` response.
+pub(crate) struct Ranges {
+    file: File,
+    parts: VecDeque<Part>,
+}
+
+enum Part {
+    // the head of a part of a `multipart/byteranges` body, or its end
+    Text(Cursor<Vec<u8>>),
+    File {
+        start: u64,
+        remaining: u64,
+        seeked: bool,
+    },
+}
+
+impl Read for Ranges {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while let Some(part) = self.parts.front_mut() {
+            let read = match part {
+                Part::Text(text) => text.read(buf)?,
+                Part::File {
+                    start,
+                    remaining,
+                    seeked,
+                } => {
+                    if !*seeked {
+                        self.file.seek(SeekFrom::Start(*start))?;
+                        *seeked = true;
+                    }
+                    let len = buf
+                        .len()
+                        .min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+                    let read = self.file.read(&mut buf[..len])?;
+                    *remaining -= read as u64;
+                    if read == 0 && *remaining != 0 {
+                        // the file was truncated, the length that was announced can't be sent
+                        return Err(std::io::ErrorKind::UnexpectedEof.into());
+                    }
+                    read
+                }
+            };
+
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            self.parts.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+/// Parses the value of a `Range` header into inclusive ranges of a body of `length` bytes.
+///
+/// Returns `None` if the header is invalid or uses another unit than bytes, in which case it
+/// is ignored, and an empty list if none of the ranges can be satisfied.
+fn parse_ranges(value: &str, length: u64) -> Option<Vec<(u64, u64)>> {
+    let (unit, specs) = value.split_once('=')?;
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        if spec.is_empty() {
+            continue;
+        }
+        let (first, last) = spec.split_once('-')?;
+        let number = |value: &str| {
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            // too large to matter
+            Some(value.parse::<u64>().unwrap_or(u64::MAX))
+        };
+
+        if first.is_empty() {
+            // the last bytes
+            let suffix = number(last)?;
+            if suffix > 0 && length > 0 {
+                ranges.push((length.saturating_sub(suffix), length - 1));
+            }
+        } else {
+            let first = number(first)?;
+            let last = match last {
+                "" => u64::MAX,
+                last => number(last)?,
+            };
+            if last < first {
+                return None;
+            }
+            if first < length {
+                ranges.push((first, last.min(length - 1)));
+            }
+        }
+    }
+
+    Some(ranges)
+}
+
+/// Sorts the ranges and merges the ones that overlap or are adjacent, so that no byte is sent
+/// twice (RFC 9110, section 14.2).
+fn coalesce(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some(previous) if first <= previous.1.saturating_add(1) => {
+                previous.1 = previous.1.max(last);
+            }
+            _ => merged.push((first, last)),
+        }
+    }
+    merged
+}
+
+/// Whether the validator of an `If-Range` header matches the response, see RFC 9110,
+/// section 13.1.5.
+fn if_range_matches(value: &str, headers: &HeaderMap) -> bool {
+    let value = value.trim();
+    if value.starts_with('"') || value.starts_with("W/") {
+        // only strong entity tags can match
+        return !value.starts_with("W/")
+            && headers
+                .get(header::ETAG)
+                .is_some_and(|etag| etag.as_bytes() == value.as_bytes());
+    }
+
+    let date = match value.parse::<HttpDate>() {
+        Ok(date) => SystemTime::from(date),
+        Err(_) => return false,
+    };
+    headers
+        .get(header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<HttpDate>().ok())
+        .is_some_and(|modified| SystemTime::from(modified) == date)
+}
+
+/// A boundary that can't be found in the parts, since it is not sent with them.
+fn boundary() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.subsec_nanos());
+    format!(
+        "tiny-http-{:08x}{:08x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed) as u32
+    )
+}
+
+/// Serves the ranges asked by the request if the response comes from a file, and advertises
+/// that ranges are supported.
+pub(crate) fn apply<R>(
+    mut response: Response<R>,
+    method: &Method,
+    request_headers: &HeaderMap,
+) -> Response<RangeBody<R>>
+where
+    R: Read,
+{
+    let length = response.data_length();
+    let file = match response.take_file() {
+        Some(file) if response.status_code() == StatusCode::OK => file,
+        _ => return response.map_data(RangeBody::Whole, length),
+    };
+    let length = match length {
+        Some(length) => length as u64,
+        None => return response.map_data(RangeBody::Whole, None),
+    };
+
+    let headers = response.headers_mut();
+    if !headers.contains_key(header::ACCEPT_RANGES) {
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    }
+
+    let range = request_headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    let if_range = request_headers
+        .get(header::IF_RANGE)
+        .and_then(|value| value.to_str().ok());
+    let ranges = match range {
+        Some(range) if *method == Method::GET => parse_ranges(range, length),
+        _ => None,
+    };
+    let ranges = match ranges {
+        Some(ranges) if ranges.len() <= MAX_RANGES => coalesce(ranges),
+        _ => return response.map_data(RangeBody::Whole, Some(length as usize)),
+    };
+    if let Some(if_range) = if_range {
+        // the representation changed, the client needs all of it
+        if !if_range_matches(if_range, headers) {
+            return response.map_data(RangeBody::Whole, Some(length as usize));
+        }
+    }
+
+    if ranges.is_empty() {
+        headers.remove(header::CONTENT_TYPE);
+        headers.insert(
+            header::CONTENT_RANGE,
+            format!("bytes */{}", length).parse().unwrap(),
+        );
+        return response
+            .with_status_code(StatusCode::RANGE_NOT_SATISFIABLE)
+            .map_data(RangeBody::Whole, Some(0));
+    }
+
+    let mut parts = VecDeque::with_capacity(ranges.len() * 2 + 1);
+    let total;
+    if let [(first, last)] = ranges[..] {
+        headers.insert(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", first, last, length)
+                .parse()
+                .unwrap(),
+        );
+        parts.push_back(Part::File {
+            start: first,
+            remaining: last - first + 1,
+            seeked: false,
+        });
+        total = last - first + 1;
+    } else {
+        let boundary = boundary();
+        let content_type = headers.remove(header::CONTENT_TYPE);
+        headers.insert(
+            header::CONTENT_TYPE,
+            format!("multipart/byteranges; boundary={}", boundary)
+                .parse()
+                .unwrap(),
+        );
+
+        let mut sum = 0;
+        for (first, last) in ranges {
+            let mut head = format!("\r\n--{}\r\n", boundary).into_bytes();
+            if let Some(ref content_type) = content_type {
+                head.extend_from_slice(b"Content-Type: ");
+                head.extend_from_slice(content_type.as_bytes());
+                head.extend_from_slice(b"\r\n");
+            }
+            head.extend_from_slice(
+                format!("Content-Range: bytes {}-{}/{}\r\n\r\n", first, last, length).as_bytes(),
+            );
+
+            sum += head.len() as u64 + last - first + 1;
+            parts.push_back(Part::Text(Cursor::new(head)));
+            parts.push_back(Part::File {
+                start: first,
+                remaining: last - first + 1,
+                seeked: false,
+            });
+        }
+        let end = format!("\r\n--{}--\r\n", boundary).into_bytes();
+        sum += end.len() as u64;
+        parts.push_back(Part::Text(Cursor::new(end)));
+        total = sum;
+    }
+
+    response
+        .with_status_code(StatusCode::PARTIAL_CONTENT)
+        .map_data(
+            |_| RangeBody::Ranges(Ranges { file, parts }),
+            Some(total as usize),
+        )
+}
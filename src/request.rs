@@ -2,14 +2,21 @@ use std::io::Error as IoError;
 use std::io::{self, Cursor, ErrorKind, Read, Write};
 
 use std::fmt;
+use std::mem;
 use std::net::SocketAddr;
 
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-use crate::util::{EqualReader, FusedReader};
-use crate::Response;
+use crate::hooks::ResponseInfo;
+use crate::log;
+use crate::util::{ConnectionGuard, EqualReader, FusedReader};
+use crate::{
+    ConnectionSettings, ListenAddr, PeerCredentials, Response, ResponseBox, StreamingWriter,
+};
 use chunked_transfer::Decoder;
-use http::{header, HeaderMap, Method, StatusCode, Uri, Version};
+use http::{header, Extensions, HeaderMap, Method, StatusCode, Uri, Version};
 
 /// Represents an HTTP request made by a client.
 ///
@@ -56,6 +63,10 @@ pub struct Request {
 
     remote_addr: Option<SocketAddr>,
 
+    local_addr: Option<ListenAddr>,
+
+    peer_credentials: Option<PeerCredentials>,
+
     // true if HTTPS, false if HTTP
     secure: bool,
 
@@ -74,6 +85,17 @@ pub struct Request {
 
     // If Some, a message must be sent after responding
     notify_when_responded: Option<Sender<()>>,
+
+    // index of the server listener that accepted the connection
+    listener_index: usize,
+
+    // keeps the connection counted by `Server::num_connections()` until the request is answered
+    connection_guard: Option<Arc<ConnectionGuard>>,
+
+    // true while the request waits in the server's queue, before being handed to the application
+    queued: bool,
+
+    extensions: Extensions,
 }
 
 struct NotifyOnDrop<R> {
@@ -103,9 +125,6 @@ impl<R> Drop for NotifyOnDrop<R> {
 /// Error that can happen when building a `Request` object.
 #[derive(Debug)]
 pub enum RequestCreationError {
-    /// The client sent an `Expect` header that was not recognized by tiny-http.
-    ExpectationFailed,
-
     /// Error while reading data from the socket during the creation of the `Request`.
     CreationIoError(IoError),
 }
@@ -116,6 +135,17 @@ impl From<IoError> for RequestCreationError {
     }
 }
 
+/// Returns `true` if the client sent an `Expect` header that is not recognized by tiny-http.
+pub(crate) fn unsupported_expectation(headers: &HeaderMap) -> bool {
+    match headers
+        .get(header::EXPECT)
+        .and_then(|value| value.to_str().ok())
+    {
+        None => false,
+        Some(v) => !v.eq_ignore_ascii_case("100-continue"),
+    }
+}
+
 /// Builds a new request.
 ///
 /// After the request line and headers have been read from the socket, a new `Request` object
@@ -133,8 +163,10 @@ pub fn new_request<R, W>(
     version: Version,
     headers: HeaderMap,
     remote_addr: Option<SocketAddr>,
+    local_addr: Option<ListenAddr>,
     mut source_data: R,
     writer: W,
+    settings: Option<&ConnectionSettings>,
 ) -> Result<Request, RequestCreationError>
 where
     R: Read + Send + 'static,
@@ -155,14 +187,10 @@ where
     };
 
     // true if the client sent a `Expect: 100-continue` header
-    let expects_continue = match headers
+    let expects_continue = headers
         .get(header::EXPECT)
         .and_then(|value| value.to_str().ok())
-    {
-        None => false,
-        Some(v) if v.eq_ignore_ascii_case("100-continue") => true,
-        _ => return Err(RequestCreationError::ExpectationFailed),
-    };
+        .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"));
 
     // true if the client sent a `Connection: upgrade` header
     let connection_upgrade = match headers
@@ -216,10 +244,27 @@ where
         Box::new(io::empty()) as Box<dyn Read + Send + 'static>
     };
 
+    // decompressing the body, which then has an unknown length
+    #[cfg(feature = "compression")]
+    let (reader, content_length, headers) = {
+        let mut headers = headers;
+        let has_body = content_length.map_or(transfer_encoding.is_some(), |len| len > 0);
+        match settings.and_then(|s| s.request_decompression.as_ref()) {
+            Some(config) if has_body && !connection_upgrade && config.is_encoded(&headers) => {
+                (config.decode(reader, &mut headers)?, None, headers)
+            }
+            _ => (reader, content_length, headers),
+        }
+    };
+    #[cfg(not(feature = "compression"))]
+    let _ = settings;
+
     Ok(Request {
         data_reader: Some(reader),
         response_writer: Some(Box::new(writer) as Box<dyn Write + Send + 'static>),
         remote_addr,
+        local_addr,
+        peer_credentials: None,
         secure,
         method,
         path,
@@ -228,6 +273,10 @@ where
         body_length: content_length,
         must_send_continue: expects_continue,
         notify_when_responded: None,
+        listener_index: 0,
+        connection_guard: None,
+        queued: false,
+        extensions: Extensions::new(),
     })
 }
 
@@ -256,6 +305,42 @@ impl Request {
         &self.headers
     }
 
+    /// Evaluates the `If-Match`, `If-None-Match`, `If-Modified-Since` and `If-Unmodified-Since`
+    /// headers against the current state of the requested resource: its entity tag, with the
+    /// quotes, and its modification time. Passing `None` for both means that the resource
+    /// doesn't exist.
+    ///
+    /// Returns the status to answer with instead of performing the method, if any:
+    /// `412 Precondition Failed`, or `304 Not Modified` for `GET` and `HEAD` requests.
+    ///
+    /// `respond()` does this by itself for the `GET` and `HEAD` requests answered with a
+    /// response built by `Response::from_file()` or given an `ETag` by `with_etag()`, but the
+    /// other methods change the resource before the response is built, so their handlers must
+    /// call this first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// # let current_etag = || Some("\"v1\"");
+    /// let request = server.recv().unwrap();
+    ///
+    /// // `If-Match: "v1"` protects from lost updates, `If-None-Match: *` from overwriting
+    /// if let Some(status) = request.check_preconditions(current_etag(), None) {
+    ///     request.respond(tiny_http::Response::empty(status)).unwrap();
+    ///     return;
+    /// }
+    /// // ... store the new content
+    /// ```
+    pub fn check_preconditions(
+        &self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Option<StatusCode> {
+        let exists = etag.is_some() || last_modified.is_some();
+        crate::conditional::check(&self.method, &self.headers, exists, etag, last_modified)
+    }
+
     /// Returns the HTTP version of the request.
     #[inline]
     pub fn http_version(&self) -> &Version {
@@ -283,6 +368,66 @@ impl Request {
         self.remote_addr.as_ref()
     }
 
+    /// Returns the local address the connection of this request was accepted on.
+    ///
+    /// For TCP listeners bound to a wildcard address (such as `0.0.0.0`), this is the specific
+    /// address and port the client connected to. For UNIX listeners, this is the path of the
+    /// listening socket. Returns `None` if the address could not be determined.
+    #[inline]
+    pub fn local_addr(&self) -> Option<&ListenAddr> {
+        self.local_addr.as_ref()
+    }
+
+    /// Returns the credentials of the process that opened the connection.
+    ///
+    /// The credentials are only known for UNIX listeners on Linux, where they are captured
+    /// when the connection is accepted. This is also available on other platforms and
+    /// listeners, for ease of use, but always returns `None`.
+    ///
+    /// Unlike headers, these cannot be forged by the client, which makes them suitable to
+    /// authorize local callers.
+    #[inline]
+    pub fn peer_credentials(&self) -> Option<&PeerCredentials> {
+        self.peer_credentials.as_ref()
+    }
+
+    /// Returns the index of the server listener that accepted the connection of this request.
+    ///
+    /// Listeners are numbered in the order they were given to the server, which is also the
+    /// order of `Server::server_addrs()`. A server with a single listener always returns `0`.
+    #[inline]
+    pub fn listener_index(&self) -> usize {
+        self.listener_index
+    }
+
+    /// Returns the extensions of the request, where data can be attached to it.
+    ///
+    /// The server stores a `ConnectionId` and an `ArrivalTime` in every request, and a `TlsInfo`
+    /// in the requests received over SSL. Other values can be added with `extensions_mut()`,
+    /// for example by a middleware that authenticates the user:
+    ///
+    /// ```
+    /// # let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    /// #[derive(Clone)]
+    /// struct User(String);
+    ///
+    /// let mut request = request;
+    /// request.extensions_mut().insert(User("alice".to_owned()));
+    ///
+    /// let connection = request.extensions().get::<tiny_http::ConnectionId>();
+    /// let user = request.extensions().get::<User>().unwrap();
+    /// ```
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns a mutable reference to the extensions of the request.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Sends a response with a `Connection: upgrade` header, then turns the `Request` into a `Stream`.
     ///
     /// The main purpose of this function is to support websockets.
@@ -293,16 +438,29 @@ impl Request {
     ///  is destroyed before continuing to read or write on the socket. Therefore you should always
     ///  destroy it as soon as possible.
     pub fn upgrade<R: Read>(
-        mut self,
+        self,
         protocol: &str,
         response: Response<R>,
     ) -> Box<dyn ReadWrite + Send> {
         use crate::util::CustomStream;
 
+        let (reader, writer) = self.upgrade_parts(protocol, response);
+        Box::new(CustomStream::new(reader, writer))
+    }
+
+    /// Same as `upgrade()`, but returns the two halves of the stream.
+    ///
+    /// The connection is released once the writer is dropped.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn upgrade_parts<R: Read>(
+        mut self,
+        protocol: &str,
+        response: Response<R>,
+    ) -> (Box<dyn Read + Send>, Box<dyn Write + Send>) {
         response
             .raw_print(
                 self.response_writer.as_mut().unwrap().by_ref(),
-                self.http_version.clone(),
+                self.http_version,
                 &self.headers,
                 false,
                 Some(protocol),
@@ -311,15 +469,16 @@ impl Request {
 
         self.response_writer.as_mut().unwrap().flush().ok(); // TODO: unused result
 
-        let stream = CustomStream::new(self.extract_reader_impl(), self.extract_writer_impl());
+        let reader = self.extract_reader_impl();
+        let writer = self.extract_writer_impl();
         if let Some(sender) = self.notify_when_responded.take() {
-            let stream = NotifyOnDrop {
+            let writer = NotifyOnDrop {
                 sender,
-                inner: stream,
+                inner: writer,
             };
-            Box::new(stream) as Box<dyn ReadWrite + Send>
+            (reader, Box::new(writer))
         } else {
-            Box::new(stream) as Box<dyn ReadWrite + Send>
+            (reader, writer)
         }
     }
 
@@ -353,7 +512,7 @@ impl Request {
             let msg = Response::new_empty(StatusCode::CONTINUE);
             msg.raw_print(
                 self.response_writer.as_mut().unwrap().by_ref(),
-                self.http_version.clone(),
+                self.http_version,
                 &self.headers,
                 true,
                 None,
@@ -390,6 +549,97 @@ impl Request {
         }
     }
 
+    /// Turns the `Request` into an `http::Request`, whose body reads the body sent by the
+    /// client, and a `Responder` to answer it.
+    ///
+    /// This allows handing the request to code written against the `http` crate. The
+    /// extensions are moved to the `http::Request`. The body and the `Responder` are
+    /// independent, so that the response can be sent while the body is still being read.
+    ///
+    /// ```no_run
+    /// # use std::io::Cursor;
+    /// # fn handle(_: http::Request<tiny_http::RequestBody>) -> http::Response<Cursor<Vec<u8>>> {
+    /// #     unimplemented!()
+    /// # }
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// let (request, responder) = server.recv().unwrap().into_http();
+    /// let response = handle(request);
+    /// let _ = responder.respond(response.into());
+    /// ```
+    pub fn into_http(self) -> (http::Request<RequestBody>, Responder) {
+        let (head, body, responder) = self.into_parts();
+        let mut request = http::Request::new(body);
+        *request.method_mut() = head.method;
+        *request.uri_mut() = head.url;
+        *request.version_mut() = head.http_version;
+        *request.headers_mut() = head.headers;
+        *request.extensions_mut() = head.extensions;
+        (request, responder)
+    }
+
+    /// Splits the `Request` into its head, its body and a `Responder` to answer it.
+    ///
+    /// The three parts are owned, so that one thread can read the body while another one
+    /// sends the response, as proxies and echo services do. The extensions are moved to the
+    /// head.
+    ///
+    /// ```no_run
+    /// # use std::thread;
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// let (head, mut body, responder) = server.recv().unwrap().into_parts();
+    ///
+    /// // echoing the body while it is received
+    /// let echo = thread::spawn(move || {
+    ///     let mut writer = responder.into_writer();
+    ///     // ... write the status line and the headers ...
+    ///     std::io::copy(&mut body, &mut writer)
+    /// });
+    /// # let _ = (head, echo.join());
+    /// ```
+    pub fn into_parts(mut self) -> (RequestHead, RequestBody, Responder) {
+        let body = self.take_body();
+        let head = RequestHead {
+            method: self.method.clone(),
+            url: self.path.clone(),
+            http_version: self.http_version,
+            headers: self.headers.clone(),
+            body_length: self.body_length,
+            remote_addr: self.remote_addr,
+            local_addr: self.local_addr.clone(),
+            peer_credentials: self.peer_credentials,
+            secure: self.secure,
+            listener_index: self.listener_index,
+            extensions: mem::take(&mut self.extensions),
+        };
+        (head, body, Responder { request: self })
+    }
+
+    /// Takes the body out of the request, leaving an empty one.
+    ///
+    /// If a `100 Continue` response is still due, the response writer is shared with the body,
+    /// which sends it on its first read unless the response has been started in the meantime.
+    fn take_body(&mut self) -> RequestBody {
+        let reader = mem::replace(self.data_reader.as_mut().unwrap(), Box::new(io::empty()));
+
+        let continue_writer = if self.must_send_continue {
+            self.must_send_continue = false;
+            let state = Arc::new(Mutex::new(ContinueState {
+                writer: self.response_writer.take(),
+                pending: true,
+            }));
+            self.response_writer = Some(Box::new(SharedWriter(state.clone())));
+            Some(state)
+        } else {
+            None
+        };
+
+        RequestBody {
+            reader,
+            continue_writer,
+            http_version: self.http_version,
+        }
+    }
+
     /// Extract the response `Writer` object from the Request, dropping this `Writer` has the same side effects
     /// as the object returned by `into_writer` above.
     ///
@@ -430,23 +680,80 @@ impl Request {
         res
     }
 
+    /// Sends the head of a response to this request, and returns a writer for its body.
+    ///
+    /// The body is sent with the same transfer encoding as the body of a `Response` of
+    /// unknown length: chunks with HTTP/1.1, and the connection is closed at the end of the
+    /// body with HTTP/1.0. The `Content-Length`, `Transfer-Encoding` and `Connection` headers
+    /// are set accordingly, and replace those in `headers`.
+    ///
+    /// ```no_run
+    /// # use std::io::Write;
+    /// # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    /// let request = server.recv().unwrap();
+    /// let mut writer = request
+    ///     .respond_streaming(http::StatusCode::OK, http::HeaderMap::new())
+    ///     .unwrap();
+    /// for i in 0..10 {
+    ///     writeln!(writer, "line {}", i).unwrap();
+    ///     writer.flush().unwrap();
+    /// }
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn respond_streaming(
+        mut self,
+        status: StatusCode,
+        headers: HeaderMap,
+    ) -> Result<StreamingWriter, IoError> {
+        let writer = self.extract_writer_impl();
+        let notify = self.notify_when_responded.take();
+        let guard = self.connection_guard.clone();
+        StreamingWriter::start(writer, &self, status, headers, guard, notify)
+    }
+
     fn respond_impl<R>(&mut self, response: Response<R>) -> Result<(), IoError>
     where
         R: Read,
     {
+        let response = crate::conditional::evaluate(response, &self.method, &self.headers);
+        let response = crate::range::apply(response, &self.method, &self.headers);
+
+        #[cfg(feature = "compression")]
+        let response = crate::compression::compress(
+            response,
+            self.connection_guard
+                .as_ref()
+                .and_then(|guard| guard.settings().compression.as_ref()),
+            &self.headers,
+        );
+
         let mut writer = self.extract_writer_impl();
 
         let do_not_send_body = self.method == Method::HEAD;
+        let status = response.status_code();
 
-        Self::ignore_client_closing_errors(response.raw_print(
+        let result = Self::ignore_client_closing_errors(response.raw_print(
             writer.by_ref(),
-            self.http_version.clone(),
+            self.http_version,
             &self.headers,
             do_not_send_body,
             None,
-        ))?;
+        ))
+        .and_then(|()| Self::ignore_client_closing_errors(writer.flush()));
 
-        Self::ignore_client_closing_errors(writer.flush())
+        if let Some(ref guard) = self.connection_guard {
+            if let Some(hooks) = guard.hooks() {
+                hooks.response_completed(&ResponseInfo {
+                    connection: guard.info(),
+                    method: &self.method,
+                    url: &self.path,
+                    status,
+                    error: result.as_ref().err(),
+                });
+            }
+        }
+
+        result
     }
 
     fn ignore_client_closing_errors(result: io::Result<()>) -> io::Result<()> {
@@ -463,6 +770,37 @@ impl Request {
         self.notify_when_responded = Some(sender);
         self
     }
+
+    pub(crate) fn with_peer_credentials(
+        mut self,
+        peer_credentials: Option<PeerCredentials>,
+    ) -> Self {
+        self.peer_credentials = peer_credentials;
+        self
+    }
+
+    pub(crate) fn with_listener_index(mut self, listener_index: usize) -> Self {
+        self.listener_index = listener_index;
+        self
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn connection_guard(&self) -> Option<&Arc<ConnectionGuard>> {
+        self.connection_guard.as_ref()
+    }
+
+    pub(crate) fn with_connection_guard(
+        mut self,
+        connection_guard: Option<Arc<ConnectionGuard>>,
+    ) -> Self {
+        self.connection_guard = connection_guard;
+        self
+    }
+
+    pub(crate) fn with_queued(mut self, queued: bool) -> Self {
+        self.queued = queued;
+        self
+    }
 }
 
 impl fmt::Debug for Request {
@@ -477,13 +815,310 @@ impl fmt::Debug for Request {
 
 impl Drop for Request {
     fn drop(&mut self) {
-        if self.response_writer.is_some() {
-            let response = Response::empty(StatusCode::INTERNAL_SERVER_ERROR);
-            let _ = self.respond_impl(response); // ignoring any potential error
+        if self.response_writer.is_none() {
+            return;
+        }
+
+        // the server was dropped before the application got the request, which isn't a bug of
+        // the application
+        if self.queued {
+            if let Some(ref guard) = self.connection_guard {
+                if let Some(hooks) = guard.hooks() {
+                    hooks.request_unanswered(self);
+                    hooks.error_response(guard.info(), StatusCode::SERVICE_UNAVAILABLE);
+                }
+            }
+            let _ = self.respond_impl(Response::empty(StatusCode::SERVICE_UNAVAILABLE).boxed());
             if let Some(sender) = self.notify_when_responded.take() {
                 let _ = sender.send(());
             }
+            if let Some(ref guard) = self.connection_guard {
+                guard.close();
+            }
+            return;
+        }
+
+        let guard = self.connection_guard.clone();
+        let default_policy = UnansweredRequestPolicy::default();
+        let policy = match guard {
+            Some(ref guard) => &guard.settings().unanswered_requests,
+            None => &default_policy,
+        };
+
+        if policy.log {
+            log::error!(
+                "Request {} {} dropped without a response",
+                self.method,
+                self.path
+            );
+        }
+
+        let response = match policy.response {
+            Some(ref response) => response(self),
+            None => Response::empty(StatusCode::INTERNAL_SERVER_ERROR).boxed(),
+        };
+
+        if let Some(ref guard) = guard {
+            if let Some(hooks) = guard.hooks() {
+                hooks.request_unanswered(self);
+                hooks.error_response(guard.info(), response.status_code());
+            }
         }
+
+        let _ = self.respond_impl(response); // ignoring any potential error
+        if let Some(sender) = self.notify_when_responded.take() {
+            let _ = sender.send(());
+        }
+
+        if policy.close_connection {
+            if let Some(ref guard) = guard {
+                guard.close();
+            }
+        }
+
+        if policy.panic_in_debug && cfg!(debug_assertions) && !std::thread::panicking() {
+            panic!(
+                "Request {} {} dropped without a response",
+                self.method, self.path
+            );
+        }
+    }
+}
+
+/// The head of a request, returned by `Request::into_parts()`.
+///
+/// The fields match the accessors of `Request`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RequestHead {
+    /// The method requested by the client, see `Request::method()`.
+    pub method: Method,
+
+    /// The resource requested by the client, see `Request::url()`.
+    pub url: Uri,
+
+    /// The HTTP version of the request, see `Request::http_version()`.
+    pub http_version: Version,
+
+    /// The headers sent by the client, see `Request::headers()`.
+    pub headers: HeaderMap,
+
+    /// The length of the body, if known, see `Request::body_length()`.
+    pub body_length: Option<usize>,
+
+    /// The address of the client, see `Request::remote_addr()`.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// The address the connection was accepted on, see `Request::local_addr()`.
+    pub local_addr: Option<ListenAddr>,
+
+    /// The credentials of the client process, see `Request::peer_credentials()`.
+    pub peer_credentials: Option<PeerCredentials>,
+
+    /// `true` if the request was made through HTTPS, see `Request::secure()`.
+    pub secure: bool,
+
+    /// The index of the listener, see `Request::listener_index()`.
+    pub listener_index: usize,
+
+    /// The extensions of the request, see `Request::extensions()`.
+    pub extensions: Extensions,
+}
+
+/// The body of a request whose response is sent separately, returned by
+/// `Request::into_parts()` and `Request::into_http()`.
+///
+/// If the client sent a `Expect: 100-continue` header, the first read sends back a
+/// `100 Continue` response, unless the response has already been started.
+pub struct RequestBody {
+    reader: Box<dyn Read + Send + 'static>,
+
+    // the response writer, while a `100 Continue` response may still be sent
+    continue_writer: Option<Arc<Mutex<ContinueState>>>,
+
+    http_version: Version,
+}
+
+impl RequestBody {
+    /// Returns the reader of the body, without sending a pending `100 Continue` response.
+    fn into_reader(mut self) -> Box<dyn Read + Send + 'static> {
+        mem::replace(&mut self.reader, Box::new(io::empty()))
+    }
+}
+
+impl Read for RequestBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(state) = self.continue_writer.take() {
+            let mut state = state.lock().unwrap();
+            if let (true, Some(writer)) = (state.pending, state.writer.as_mut()) {
+                let msg = Response::new_empty(StatusCode::CONTINUE);
+                msg.raw_print(
+                    writer.by_ref(),
+                    self.http_version,
+                    &HeaderMap::new(),
+                    true,
+                    None,
+                )
+                .ok();
+                writer.flush().ok();
+            }
+            state.pending = false;
+        }
+
+        self.reader.read(buf)
+    }
+}
+
+impl fmt::Debug for RequestBody {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("RequestBody")
+    }
+}
+
+struct ContinueState {
+    // `None` once the response has been written
+    writer: Option<Box<dyn Write + Send + 'static>>,
+    // true until the `100 Continue` response is sent or the response is started
+    pending: bool,
+}
+
+/// Response writer shared with a `RequestBody` that may send a `100 Continue` response.
+struct SharedWriter(Arc<Mutex<ContinueState>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.0.lock().unwrap();
+        state.pending = false;
+        match state.writer {
+            Some(ref mut writer) => writer.write(buf),
+            None => Err(IoError::new(
+                ErrorKind::NotConnected,
+                "response already sent",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.lock().unwrap().writer {
+            Some(ref mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SharedWriter {
+    fn drop(&mut self) {
+        // releases the connection for the next response, even if the body is still around
+        let mut state = self.0.lock().unwrap();
+        state.pending = false;
+        state.writer = None;
+    }
+}
+
+/// Sends the response to a request whose body is read separately, returned by
+/// `Request::into_parts()` and `Request::into_http()`.
+///
+/// Dropping a `Responder` without answering has the same effect as dropping a `Request`:
+/// the response of the `UnansweredRequestPolicy` is sent. Responses to pipelined requests are
+/// still sent in order, and over SSL the next request is only read once this one has been
+/// answered.
+pub struct Responder {
+    // the request, with an empty body
+    request: Request,
+}
+
+impl Responder {
+    /// Sends a response to the request. See `Request::respond()`.
+    #[inline]
+    pub fn respond<R>(self, response: Response<R>) -> Result<(), IoError>
+    where
+        R: Read,
+    {
+        self.request.respond(response)
+    }
+
+    /// Turns the `Responder` into a writer. See `Request::into_writer()`.
+    #[inline]
+    pub fn into_writer(self) -> Box<dyn Write + Send + 'static> {
+        self.request.into_writer()
+    }
+
+    /// Sends the head of a response, and returns a writer for its body. See
+    /// `Request::respond_streaming()`.
+    #[inline]
+    pub fn respond_streaming(
+        self,
+        status: StatusCode,
+        headers: HeaderMap,
+    ) -> Result<StreamingWriter, IoError> {
+        self.request.respond_streaming(status, headers)
+    }
+
+    /// Sends a response with a `Connection: upgrade` header, then gives full control of the
+    /// connection. See `Request::upgrade()`.
+    ///
+    /// The body must be given back, since it is how the data sent by the client after the
+    /// upgrade is read.
+    pub fn upgrade<R: Read>(
+        mut self,
+        protocol: &str,
+        response: Response<R>,
+        body: RequestBody,
+    ) -> Box<dyn ReadWrite + Send> {
+        self.request.data_reader = Some(body.into_reader());
+        self.request.upgrade(protocol, response)
+    }
+}
+
+impl fmt::Debug for Responder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            formatter,
+            "Responder({} {} from {:?})",
+            self.request.method, self.request.path, self.request.remote_addr
+        )
+    }
+}
+
+type UnansweredResponse = dyn Fn(&Request) -> ResponseBox + Send + Sync;
+
+/// What the server does when a `Request` is dropped without a response, for example because
+/// a handler returned early.
+///
+/// By default, an empty `500 Internal Server Error` response is sent and nothing else happens.
+/// In every case, `ServerHooks::request_unanswered()` is called.
+///
+/// The policy doesn't apply to the requests that were never handed to the application, because
+/// the `Server` was dropped while they were queued: they get an empty
+/// `503 Service Unavailable` response, and their connection is closed. The hook is still
+/// called for them.
+#[derive(Clone, Default)]
+pub struct UnansweredRequestPolicy {
+    /// Builds the response to send instead of the empty `500 Internal Server Error`.
+    pub response: Option<Arc<UnansweredResponse>>,
+
+    /// If `true`, logs an error with the method and the path of the request.
+    pub log: bool,
+
+    /// If `true`, no other request is read from the connection, which is closed once the
+    /// requests already read have been answered.
+    pub close_connection: bool,
+
+    /// If `true`, panics after sending the response when the crate is built with debug
+    /// assertions, so that the mistake can't go unnoticed during development. Requests
+    /// dropped while a thread is already panicking are left alone.
+    pub panic_in_debug: bool,
+}
+
+impl fmt::Debug for UnansweredRequestPolicy {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("UnansweredRequestPolicy")
+            .field("response", &self.response.as_ref().map(|_| "Fn"))
+            .field("log", &self.log)
+            .field("close_connection", &self.close_connection)
+            .field("panic_in_debug", &self.panic_in_debug)
+            .finish()
     }
 }
 
@@ -1,4 +1,4 @@
-use http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode, Version};
+use http::{header, Extensions, HeaderMap, HeaderName, HeaderValue, StatusCode, Version};
 use httpdate::HttpDate;
 use std::cmp::Ordering;
 use std::sync::mpsc::Receiver;
@@ -11,6 +11,8 @@ use std::fs::File;
 use std::str::FromStr;
 use std::time::SystemTime;
 
+use crate::conditional;
+
 /// Object representing an HTTP response whose purpose is to be given to a `Request`.
 ///
 /// Some headers cannot be changed. Trying to define the value
@@ -24,18 +26,28 @@ use std::time::SystemTime;
 /// Some headers have special behaviors:
 ///
 ///  - `Content-Encoding`: If you define this header, the library
-///     will assume that the data from the `Read` object has the specified encoding
-///     and will just pass-through.
+///    will assume that the data from the `Read` object has the specified encoding
+///    and will just pass-through.
 ///
 ///  - `Content-Length`: The length of the data should be set manually
-///     using the `Reponse` object's API. Attempting to set the value of this
-///     header will be equivalent to modifying the size of the data but the header
-///     itself may not be present in the final result.
+///    using the `Reponse` object's API. Attempting to set the value of this
+///    header will be equivalent to modifying the size of the data but the header
+///    itself may not be present in the final result.
 ///
 ///  - `Content-Type`: You may only set this header to one value at a time. If you
-///     try to set it more than once, the existing value will be overwritten. This
-///     behavior differs from the default for most headers, which is to allow them to
-///     be set multiple times in the same response.
+///    try to set it more than once, the existing value will be overwritten. This
+///    behavior differs from the default for most headers, which is to allow them to
+///    be set multiple times in the same response.
+///
+///  - `ETag` and `Last-Modified`: Like `Content-Type`, they only have one value.
+///    `from_file()` sets them automatically, and `with_etag()` computes the `ETag` of the
+///    responses built by `from_data()` and `from_string()`. When such a
+///    response is given to `Request::respond()` for a `GET` or `HEAD` request, the
+///    `If-Match`, `If-None-Match`, `If-Modified-Since` and `If-Unmodified-Since` headers of
+///    the request are checked against them, and a successful response is turned into a
+///    `304 Not Modified` or a `412 Precondition Failed` response when they say so. The other
+///    methods change the resource before the response is built, so their handlers must call
+///    `Request::check_preconditions()` first.
 ///
 pub struct Response<R> {
     reader: R,
@@ -43,6 +55,12 @@ pub struct Response<R> {
     headers: HeaderMap,
     data_length: Option<usize>,
     chunked_threshold: Option<usize>,
+    extensions: Extensions,
+    // the file the data is read from, to serve ranges of it
+    file: Option<File>,
+    // true if the validators were computed by `from_file()` or `with_etag()`, in which case
+    // the preconditions of `GET` and `HEAD` requests are evaluated against them
+    conditional: bool,
 }
 
 /// A `Response` without a template parameter.
@@ -51,7 +69,7 @@ pub type ResponseBox = Response<Box<dyn Read + Send>>;
 /// Transfer encoding to use when sending the message.
 /// Note that only *supported* encoding are listed here.
 #[derive(Copy, Clone)]
-enum TransferEncoding {
+pub(crate) enum TransferEncoding {
     Identity,
     Chunked,
 }
@@ -71,12 +89,12 @@ impl FromStr for TransferEncoding {
 }
 
 /// Builds a Date: header with the current date.
-fn date_header_value() -> HeaderValue {
+pub(crate) fn date_header_value() -> HeaderValue {
     let d = HttpDate::from(SystemTime::now());
     d.to_string().parse().unwrap()
 }
 
-fn write_message_header<W>(
+pub(crate) fn write_message_header<W>(
     mut writer: W,
     http_version: &Version,
     status_code: &StatusCode,
@@ -102,7 +120,7 @@ where
     Ok(())
 }
 
-fn choose_transfer_encoding(
+pub(crate) fn choose_transfer_encoding(
     status_code: StatusCode,
     request_headers: &HeaderMap,
     http_version: &Version,
@@ -124,6 +142,11 @@ fn choose_transfer_encoding(
         return TransferEncoding::Identity;
     }
 
+    // a 304 (Not Modified) response has no body, but can tell the length it would have had
+    if status_code == StatusCode::NOT_MODIFIED && entity_length.is_some() {
+        return TransferEncoding::Identity;
+    }
+
     // parsing the request's TE header
     let user_request = request_headers
         // finding TE
@@ -198,6 +221,9 @@ where
             headers: HeaderMap::with_capacity(16),
             data_length,
             chunked_threshold: None,
+            extensions: Extensions::new(),
+            file: None,
+            conditional: false,
         };
 
         // TODO: this can probably be done with some kind of filtering instead
@@ -267,9 +293,12 @@ where
             }
 
             return;
-        // if the header is Content-Type and it's already set, overwrite it
-        } else if name == header::CONTENT_TYPE {
-            let _ = self.headers.insert(header::CONTENT_TYPE, value);
+        // if the header is Content-Type, ETag or Last-Modified and it's already set, overwrite it
+        } else if name == header::CONTENT_TYPE
+            || name == header::ETAG
+            || name == header::LAST_MODIFIED
+        {
+            let _ = self.headers.insert(name, value);
             return;
         }
 
@@ -307,6 +336,27 @@ where
             status_code: self.status_code,
             data_length,
             chunked_threshold: self.chunked_threshold,
+            extensions: self.extensions,
+            file: None,
+            conditional: false,
+        }
+    }
+
+    /// Returns the same response, but with its data passed through `map`.
+    pub(crate) fn map_data<S, F>(self, map: F, data_length: Option<usize>) -> Response<S>
+    where
+        F: FnOnce(R) -> S,
+        S: Read,
+    {
+        Response {
+            reader: map(self.reader),
+            headers: self.headers,
+            status_code: self.status_code,
+            data_length,
+            chunked_threshold: self.chunked_threshold,
+            extensions: self.extensions,
+            file: None,
+            conditional: self.conditional,
         }
     }
 
@@ -460,6 +510,35 @@ where
     pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
+
+    /// Headers of the response, without the checks of `add_header()`.
+    pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Whether the preconditions of `GET` and `HEAD` requests are evaluated against the
+    /// validators of the response.
+    pub(crate) fn is_conditional(&self) -> bool {
+        self.conditional
+    }
+
+    /// Takes the file the data is read from, if the response was built by `from_file()`.
+    pub(crate) fn take_file(&mut self) -> Option<File> {
+        self.file.take()
+    }
+
+    /// Returns the extensions of the response.
+    ///
+    /// They are not sent to the client, but carry data alongside the response, for example
+    /// from the `http::Response` it was converted from.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns a mutable reference to the extensions of the response.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
 }
 
 impl<R> Response<R>
@@ -474,6 +553,9 @@ where
             headers: self.headers,
             data_length: self.data_length,
             chunked_threshold: self.chunked_threshold,
+            extensions: self.extensions,
+            file: self.file,
+            conditional: self.conditional,
         }
     }
 }
@@ -483,14 +565,41 @@ impl Response<File> {
     ///
     /// The `Content-Type` will **not** be automatically detected,
     ///  you must set it yourself.
+    ///
+    /// When given to `Request::respond()`, the response has an `Accept-Ranges: bytes` header
+    /// and answers `GET` requests with a `Range` header: a single range is sent as a
+    /// `206 Partial Content` response with a `Content-Range` header, several ones as a
+    /// `multipart/byteranges` body, and ranges outside of the file get a
+    /// `416 Range Not Satisfiable` response. A `Range` with an `If-Range` header that doesn't
+    /// match the `ETag` or `Last-Modified` headers of the response is ignored. This only
+    /// happens for `200 OK` responses, and not once the data is replaced with `with_data()`.
+    ///
+    /// The `Last-Modified` header is the modification time of the file, and the `ETag` is
+    /// built from this time and the size of the file.
     pub fn from_file(file: File) -> Response<File> {
-        let file_size = file.metadata().ok().map(|v| v.len() as usize);
+        let metadata = file.metadata().ok();
+        let file_size = metadata.as_ref().map(|v| v.len() as usize);
+        let ranges_file = file.try_clone().ok();
+
+        let mut headers = HeaderMap::new();
+        let modified = metadata.as_ref().and_then(|v| v.modified().ok());
+        if let Some(modified) = modified {
+            let length = metadata.as_ref().map_or(0, |v| v.len());
+            let etag = conditional::etag_of_file(modified, length);
+            headers.insert(header::ETAG, etag.parse().unwrap());
+            let last_modified = HttpDate::from(modified).to_string();
+            headers.insert(header::LAST_MODIFIED, last_modified.parse().unwrap());
+        }
 
-        Response::new(StatusCode::OK, HeaderMap::new(), file, file_size, None)
+        let mut response = Response::new(StatusCode::OK, headers, file, file_size, None);
+        response.file = ranges_file;
+        response.conditional = modified.is_some();
+        response
     }
 }
 
 impl Response<Cursor<Vec<u8>>> {
+    /// Builds a new `Response` from some data.
     pub fn from_data<D>(data: D) -> Response<Cursor<Vec<u8>>>
     where
         D: Into<Vec<u8>>,
@@ -507,6 +616,7 @@ impl Response<Cursor<Vec<u8>>> {
         )
     }
 
+    /// Builds a new `text/plain` `Response` from a string.
     pub fn from_string<S>(data: S) -> Response<Cursor<Vec<u8>>>
     where
         S: Into<String>,
@@ -514,18 +624,32 @@ impl Response<Cursor<Vec<u8>>> {
         let data = data.into();
         let data_len = data.len();
 
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=UTF-8"),
+        );
+
         Response::new(
             StatusCode::OK,
-            std::iter::once((
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("text/plain; charset=UTF-8"),
-            ))
-            .collect(),
+            headers,
             Cursor::new(data.into_bytes()),
             Some(data_len),
             None,
         )
     }
+
+    /// Sets an `ETag` header computed from the data, which means hashing all of it.
+    ///
+    /// The `If-Match` and `If-None-Match` headers of `GET` and `HEAD` requests are then
+    /// checked against it by `Request::respond()`, which can answer with a `304 Not Modified`
+    /// response instead of sending the data again.
+    pub fn with_etag(mut self) -> Response<Cursor<Vec<u8>>> {
+        let etag = conditional::etag_of_data(self.reader.get_ref());
+        self.headers.insert(header::ETAG, etag.parse().unwrap());
+        self.conditional = true;
+        self
+    }
 }
 
 impl Response<io::Empty> {
@@ -549,6 +673,23 @@ impl Response<io::Empty> {
     }
 }
 
+impl<B> From<http::Response<B>> for Response<B>
+where
+    B: Read,
+{
+    /// Converts a response of the `http` crate, keeping its extensions.
+    ///
+    /// The headers follow the same rules as `Response::add_header()`: for example a
+    /// `Content-Length` header sets the length of the data. The HTTP version is ignored, since
+    /// responses use the version of the request.
+    fn from(response: http::Response<B>) -> Response<B> {
+        let (parts, body) = response.into_parts();
+        let mut response = Response::new(parts.status, parts.headers, body, None, None);
+        response.extensions = parts.extensions;
+        response
+    }
+}
+
 impl Clone for Response<io::Empty> {
     fn clone(&self) -> Response<io::Empty> {
         Response {
@@ -557,6 +698,9 @@ impl Clone for Response<io::Empty> {
             headers: self.headers.clone(),
             data_length: self.data_length,
             chunked_threshold: self.chunked_threshold,
+            extensions: self.extensions.clone(),
+            file: None,
+            conditional: self.conditional,
         }
     }
 }
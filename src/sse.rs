@@ -0,0 +1,293 @@
+//! Server-Sent Events, streams of events that browsers receive with `EventSource`.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tiny_http::sse::Event;
+//!
+//! # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+//! let request = server.recv().unwrap();
+//! let mut next_id = request
+//!     .last_event_id()
+//!     .and_then(|id| id.parse::<u64>().ok())
+//!     .map_or(0, |id| id + 1);
+//!
+//! let mut events = request
+//!     .respond_events(http::HeaderMap::new())
+//!     .unwrap()
+//!     .with_keep_alive(Duration::from_secs(15));
+//! loop {
+//!     let event = Event::new("tick").with_event("clock").with_id(next_id.to_string());
+//!     if events.send(&event).is_err() {
+//!         break; // the client went away
+//!     }
+//!     next_id += 1;
+//!     std::thread::sleep(Duration::from_secs(1));
+//! }
+//! ```
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+
+use crate::{Request, StreamingWriter};
+
+impl Request {
+    /// Returns the value of the `Last-Event-ID` header, sent by clients that reconnect to an
+    /// event stream with the id of the last event they received.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.headers()
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Answers the request with a `text/event-stream` response, and returns the stream to
+    /// send the events on.
+    ///
+    /// `Content-Type` is set, and `Cache-Control: no-cache` unless `headers` contains a
+    /// `Cache-Control` header. The body is sent as in `respond_streaming()`.
+    pub fn respond_events(self, mut headers: HeaderMap) -> IoResult<EventStream> {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        if let header::Entry::Vacant(entry) = headers.entry(header::CACHE_CONTROL) {
+            entry.insert(HeaderValue::from_static("no-cache"));
+        }
+
+        let writer = self.respond_streaming(StatusCode::OK, headers)?;
+        Ok(EventStream {
+            shared: Arc::new(Shared {
+                writer: Mutex::new(Some(writer)),
+                closed: AtomicBool::new(false),
+                keep_alive: Mutex::new(KeepAlive {
+                    interval: None,
+                    last_write: Instant::now(),
+                }),
+                wake: Condvar::new(),
+            }),
+        })
+    }
+}
+
+/// An event sent on an `EventStream`.
+///
+/// Line breaks are removed from the name and the id, and the data is sent as one `data`
+/// field per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Builds an event with the given data, of the default `message` type.
+    pub fn new<D>(data: D) -> Event
+    where
+        D: Into<String>,
+    {
+        Event {
+            data: Some(data.into()),
+            ..Event::default()
+        }
+    }
+
+    /// Sets the type of the event, for `addEventListener()` on the client.
+    pub fn with_event<E>(mut self, event: E) -> Event
+    where
+        E: Into<String>,
+    {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Sets the id of the event, which the client sends back in the `Last-Event-ID` header
+    /// when it reconnects.
+    pub fn with_id<I>(mut self, id: I) -> Event
+    where
+        I: Into<String>,
+    {
+        // ids containing NUL are ignored by the clients
+        self.id = Some(single_line(id.into()).replace('\0', ""));
+        self
+    }
+
+    /// Sets how long the client waits before reconnecting if the connection is lost.
+    pub fn with_retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Encodes the event, ended by an empty line.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if let Some(ref event) = self.event {
+            bytes.extend_from_slice(format!("event: {}\n", event).as_bytes());
+        }
+        if let Some(ref id) = self.id {
+            bytes.extend_from_slice(format!("id: {}\n", id).as_bytes());
+        }
+        if let Some(retry) = self.retry {
+            bytes.extend_from_slice(format!("retry: {}\n", retry.as_millis()).as_bytes());
+        }
+        if let Some(ref data) = self.data {
+            for line in data.split("\r\n").flat_map(|line| line.split(['\r', '\n'])) {
+                bytes.extend_from_slice(format!("data: {}\n", line).as_bytes());
+            }
+        }
+        bytes.push(b'\n');
+        bytes
+    }
+}
+
+fn single_line(value: String) -> String {
+    value.replace(['\r', '\n'], "")
+}
+
+/// Stream of events sent to a client, returned by `Request::respond_events()`.
+///
+/// Each event is sent as soon as it is written. A client that went away is noticed when
+/// writing to the stream: the write fails and `is_closed()` returns `true`. Keep-alive
+/// comments, enabled with `with_keep_alive()`, notice it even if no event is sent, and keep
+/// proxies from closing an idle connection.
+///
+/// The response ends when the stream is dropped.
+pub struct EventStream {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    // `None` once the stream is closed
+    writer: Mutex<Option<StreamingWriter>>,
+    // set once the stream is closed, readable while a write is blocked
+    closed: AtomicBool,
+    keep_alive: Mutex<KeepAlive>,
+    // wakes up the keep-alive thread when the interval changes or the stream is closed
+    wake: Condvar,
+}
+
+struct KeepAlive {
+    // `Some` once the keep-alive thread is started
+    interval: Option<Duration>,
+    last_write: Instant,
+}
+
+impl Shared {
+    fn write(&self, writer: &mut Option<StreamingWriter>, data: &[u8]) -> IoResult<()> {
+        let result = match *writer {
+            Some(ref mut writer) => writer.write_all(data).and_then(|()| writer.flush()),
+            None => {
+                return Err(IoError::new(
+                    IoErrorKind::NotConnected,
+                    "event stream closed",
+                ))
+            }
+        };
+
+        let mut keep_alive = self.keep_alive.lock().unwrap();
+        keep_alive.last_write = Instant::now();
+        if result.is_err() {
+            *writer = None;
+            self.closed.store(true, Ordering::SeqCst);
+            self.wake.notify_all();
+        }
+        result
+    }
+}
+
+impl EventStream {
+    /// Sends a comment whenever nothing was sent for `interval`, from a background thread.
+    /// Calling it again changes the interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_keep_alive(self, interval: Duration) -> EventStream {
+        assert!(
+            interval > Duration::ZERO,
+            "the keep-alive interval can't be zero"
+        );
+
+        let started = self
+            .shared
+            .keep_alive
+            .lock()
+            .unwrap()
+            .interval
+            .replace(interval)
+            .is_some();
+        if started {
+            self.shared.wake.notify_all();
+            return self;
+        }
+
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            let mut keep_alive = shared.keep_alive.lock().unwrap();
+            while !shared.closed.load(Ordering::SeqCst) {
+                let interval = keep_alive.interval.unwrap_or(interval);
+                let idle = keep_alive.last_write.elapsed();
+                if idle < interval {
+                    keep_alive = shared
+                        .wake
+                        .wait_timeout(keep_alive, interval - idle)
+                        .unwrap()
+                        .0;
+                    continue;
+                }
+
+                // the lock isn't held while writing, and an event being sent makes the comment
+                // useless, so that a stalled client never blocks the other methods
+                keep_alive.last_write = Instant::now();
+                drop(keep_alive);
+                if let Ok(mut writer) = shared.writer.try_lock() {
+                    let _ = shared.write(&mut writer, b":\n\n");
+                }
+                keep_alive = shared.keep_alive.lock().unwrap();
+            }
+        });
+        self
+    }
+
+    /// Sends an event, and flushes it to the client.
+    pub fn send(&mut self, event: &Event) -> IoResult<()> {
+        let mut writer = self.shared.writer.lock().unwrap();
+        self.shared.write(&mut writer, &event.to_bytes())
+    }
+
+    /// Sends a comment, which the clients ignore.
+    pub fn comment(&mut self, comment: &str) -> IoResult<()> {
+        let mut bytes = Vec::new();
+        for line in comment
+            .split("\r\n")
+            .flat_map(|line| line.split(['\r', '\n']))
+        {
+            bytes.extend_from_slice(format!(": {}\n", line).as_bytes());
+        }
+        bytes.push(b'\n');
+        let mut writer = self.shared.writer.lock().unwrap();
+        self.shared.write(&mut writer, &bytes)
+    }
+
+    /// Returns `true` once writing to the client failed, usually because it went away.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        {
+            let _keep_alive = self.shared.keep_alive.lock().unwrap();
+            self.shared.closed.store(true, Ordering::SeqCst);
+            self.shared.wake.notify_all();
+        }
+        let writer = self.shared.writer.lock().unwrap().take();
+        drop(writer);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::connection::Connection;
+use crate::connection::{Connection, ListenAddr, PeerCredentials};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::TlsInfo;
 use std::error::Error;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
@@ -22,6 +23,22 @@ impl NativeTlsStream {
             .peer_addr()
     }
 
+    pub(crate) fn peer_credentials(&mut self) -> std::io::Result<Option<PeerCredentials>> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .peer_credentials()
+    }
+
+    pub(crate) fn local_addr(&mut self) -> std::io::Result<ListenAddr> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .get_ref()
+            .local_addr()
+    }
+
     pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
         self.0
             .lock()
@@ -29,6 +46,11 @@ impl NativeTlsStream {
             .get_mut()
             .shutdown(how)
     }
+
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        // native-tls doesn't expose the outcome of the handshake
+        TlsInfo::default()
+    }
 }
 
 impl Read for NativeTlsStream {
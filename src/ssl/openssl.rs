@@ -1,5 +1,6 @@
-use crate::connection::Connection;
+use crate::connection::{Connection, ListenAddr, PeerCredentials};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::TlsInfo;
 use std::error::Error;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
@@ -19,6 +20,14 @@ impl SplitOpenSslStream {
         self.0.lock().unwrap().inner.get_mut().peer_addr()
     }
 
+    pub(crate) fn peer_credentials(&mut self) -> std::io::Result<Option<PeerCredentials>> {
+        self.0.lock().unwrap().inner.get_ref().peer_credentials()
+    }
+
+    pub(crate) fn local_addr(&mut self) -> std::io::Result<ListenAddr> {
+        self.0.lock().unwrap().inner.get_ref().local_addr()
+    }
+
     pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
         self.0.lock().unwrap().inner.get_mut().shutdown(how)
     }
@@ -46,6 +55,20 @@ impl Write for SplitOpenSslStream {
     }
 }
 
+impl OpenSslStream {
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        use openssl::ssl::NameType;
+
+        let ssl = self.inner.ssl();
+        TlsInfo {
+            protocol_version: Some(ssl.version_str().to_owned()),
+            cipher_suite: ssl.current_cipher().map(|cipher| cipher.name().to_owned()),
+            server_name: ssl.servername(NameType::HOST_NAME).map(str::to_owned),
+            alpn_protocol: ssl.selected_alpn_protocol().map(<[u8]>::to_vec),
+        }
+    }
+}
+
 impl Read for OpenSslStream {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.inner.read(buf)
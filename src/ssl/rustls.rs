@@ -1,5 +1,6 @@
-use crate::connection::Connection;
+use crate::connection::{Connection, ListenAddr, PeerCredentials};
 use crate::util::refined_tcp_stream::Stream as RefinedStream;
+use crate::TlsInfo;
 use std::error::Error;
 use std::io::{Cursor, Read, Write};
 use std::net::{Shutdown, SocketAddr};
@@ -22,6 +23,22 @@ impl RustlsStream {
             .peer_addr()
     }
 
+    pub(crate) fn peer_credentials(&mut self) -> std::io::Result<Option<PeerCredentials>> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .peer_credentials()
+    }
+
+    pub(crate) fn local_addr(&mut self) -> std::io::Result<ListenAddr> {
+        self.0
+            .lock()
+            .expect("Failed to lock SSL stream mutex")
+            .sock
+            .local_addr()
+    }
+
     pub(crate) fn shutdown(&mut self, how: Shutdown) -> std::io::Result<()> {
         self.0
             .lock()
@@ -29,6 +46,24 @@ impl RustlsStream {
             .sock
             .shutdown(how)
     }
+
+    pub(crate) fn tls_info(&self) -> TlsInfo {
+        let stream = self.0.lock().expect("Failed to lock SSL stream mutex");
+        let connection = &stream.conn;
+        TlsInfo {
+            protocol_version: connection
+                .protocol_version()
+                .and_then(|version| version.as_str())
+                // `TLSv1_3` to `TLSv1.3`, like the other implementations
+                .map(|version| version.replace('_', ".")),
+            cipher_suite: connection
+                .negotiated_cipher_suite()
+                .and_then(|suite| suite.suite().as_str())
+                .map(str::to_owned),
+            server_name: connection.server_name().map(str::to_owned),
+            alpn_protocol: connection.alpn_protocol().map(<[u8]>::to_vec),
+        }
+    }
 }
 
 impl Clone for RustlsStream {
@@ -71,7 +106,6 @@ impl RustlsContext {
     ) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let mut cursor = Cursor::new(certificates);
         let certificate_chain = rustls_pemfile::certs(&mut cursor)
-            .into_iter()
             .collect::<Result<Vec<rustls_pki_types::CertificateDer<'_>>, std::io::Error>>()?;
 
         if certificate_chain.is_empty() {
@@ -82,14 +116,16 @@ impl RustlsContext {
             let pkcs8_keys = rustls_pemfile::pkcs8_private_keys(
                 &mut private_key.clone().as_slice(),
             )
-            .into_iter()
             .collect::<Result<Vec<rustls_pki_types::PrivatePkcs8KeyDer<'_>>, std::io::Error>>()?;
 
             if let Some(pkcs8_key) = pkcs8_keys.first() {
                 pkcs8_key.clone_key().into()
             } else {
-                let rsa_keys = rustls_pemfile::rsa_private_keys(&mut private_key.as_slice()).into_iter()
-                    .collect::<Result<Vec<rustls_pki_types::PrivatePkcs1KeyDer<'_>>, std::io::Error>>()?;
+                let rsa_keys = rustls_pemfile::rsa_private_keys(&mut private_key.as_slice())
+                    .collect::<Result<
+                    Vec<rustls_pki_types::PrivatePkcs1KeyDer<'_>>,
+                    std::io::Error,
+                >>()?;
 
                 rsa_keys[0].clone_key().into()
             }
@@ -107,9 +143,13 @@ impl RustlsContext {
         stream: Connection,
     ) -> Result<RustlsStream, Box<dyn Error + Send + Sync + 'static>> {
         let connection = rustls::ServerConnection::new(self.0.clone())?;
-        Ok(RustlsStream(Arc::new(Mutex::new(
-            rustls::StreamOwned::new(connection, stream),
-        ))))
+        let mut stream = rustls::StreamOwned::new(connection, stream);
+        // completing the handshake right away like the other implementations, so that its
+        // outcome is known before the first request
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(RustlsStream(Arc::new(Mutex::new(stream))))
     }
 }
 
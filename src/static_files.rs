@@ -0,0 +1,360 @@
+//! Serving the files of a directory.
+
+use std::fs::{self, File};
+use std::io::Result as IoResult;
+use std::path::{Component, Path, PathBuf};
+
+use http::{header, HeaderValue, Method, StatusCode};
+
+use crate::util;
+use crate::{Request, Response, ResponseBox};
+
+mod listing;
+
+/// Answers requests with the files found under a root directory.
+///
+/// The path of the URL is percent-decoded and normalized before being looked up: `.` and
+/// `..` segments are resolved, and requests that would leave the root directory find
+/// nothing. A directory is served through its index file, and a request for a directory
+/// without a trailing slash is redirected to the same path with one, so that relative links
+/// work. A directory without an index file can be answered with the list of its entries, see
+/// `with_listing()`.
+///
+/// Files are sent with `Response::from_file()`, which handles conditional and range
+/// requests, and with a `Content-Type` guessed from their extension.
+///
+/// ```no_run
+/// let server = tiny_http::Server::http("0.0.0.0:8000").unwrap();
+/// let files = tiny_http::StaticFiles::new("/var/www");
+///
+/// server.serve(
+///     |request| {
+///         let _ = files.serve(request);
+///     },
+///     4,
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index_files: Vec<String>,
+    follow_symlinks: bool,
+    precompressed: bool,
+    listing: bool,
+    hidden_files: bool,
+}
+
+/// What a request resolved to.
+enum Target {
+    File(PathBuf),
+    Redirect(String),
+    // a directory, and its normalized URL path
+    Listing(PathBuf, String),
+}
+
+impl StaticFiles {
+    /// Serves the files under `root`.
+    pub fn new<P>(root: P) -> StaticFiles
+    where
+        P: Into<PathBuf>,
+    {
+        StaticFiles {
+            root: root.into(),
+            index_files: vec!["index.html".to_owned()],
+            follow_symlinks: true,
+            precompressed: false,
+            listing: false,
+            hidden_files: false,
+        }
+    }
+
+    /// Sets the files served for a directory, the first one that exists is used. Defaults
+    /// to `index.html`.
+    pub fn with_index_files<I, S>(mut self, index_files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.index_files = index_files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether symbolic links under the root directory are followed. When they are not,
+    /// a path that goes through a symbolic link finds nothing. Defaults to `true`.
+    pub fn with_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets whether the compressed copies of the files are served: `style.css.br` or
+    /// `style.css.gz` is sent instead of `style.css` to the clients that accept brotli or
+    /// gzip, with a `Content-Encoding` header. Defaults to `false`.
+    pub fn with_precompressed(mut self, precompressed: bool) -> Self {
+        self.precompressed = precompressed;
+        self
+    }
+
+    /// Sets whether the directories without an index file are answered with the list of their
+    /// entries, instead of finding nothing. Defaults to `false`.
+    ///
+    /// The list is an HTML page, or a JSON document for the clients that prefer
+    /// `application/json` in their `Accept` header:
+    ///
+    /// ```json
+    /// {"path":"/docs/","entries":[{"name":"guide.txt","type":"text/plain","size":1234,"modified":1445412480}]}
+    /// ```
+    ///
+    /// where `modified` is in seconds since the Unix epoch, and `type` is `directory` for a
+    /// directory, whose `size` is `null`. Directories come first, then the entries are sorted
+    /// by the `sort` query parameter (`name`, `size`, `modified` or `type`, defaults to
+    /// `name`), in the order of the `order` parameter (`asc` or `desc`).
+    pub fn with_listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// Sets whether the listings show the entries whose name starts with a dot. Defaults to
+    /// `false`.
+    pub fn with_hidden_files(mut self, hidden_files: bool) -> Self {
+        self.hidden_files = hidden_files;
+        self
+    }
+
+    /// Returns the response to a request, or `None` if it doesn't name a file under the
+    /// root directory.
+    ///
+    /// Requests with another method than `GET` or `HEAD` get a
+    /// `405 Method Not Allowed` response.
+    pub fn response(&self, request: &Request) -> Option<ResponseBox> {
+        let target = self.resolve(request.url().path())?;
+
+        if *request.method() != Method::GET && *request.method() != Method::HEAD {
+            return Some(
+                Response::empty(StatusCode::METHOD_NOT_ALLOWED)
+                    .with_header(header::ALLOW, HeaderValue::from_static("GET, HEAD"))
+                    .boxed(),
+            );
+        }
+
+        match target {
+            Target::File(path) => self.file_response(&path, request),
+            Target::Redirect(mut location) => {
+                if let Some(query) = request.url().query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+                Some(
+                    Response::empty(StatusCode::MOVED_PERMANENTLY)
+                        .with_header(header::LOCATION, location.parse().ok()?)
+                        .boxed(),
+                )
+            }
+            Target::Listing(directory, url_path) => {
+                listing::response(self, &directory, &url_path, request)
+            }
+        }
+    }
+
+    /// Answers a request with its file, or with `404 Not Found`.
+    pub fn serve(&self, request: Request) -> IoResult<()> {
+        match self.response(&request) {
+            Some(response) => request.respond(response),
+            None => request.respond(Response::empty(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    /// Finds the file or the redirection for the path of a URL.
+    fn resolve(&self, url_path: &str) -> Option<Target> {
+        let relative = url_path.strip_prefix('/')?;
+        let trailing_slash = relative.is_empty() || relative.ends_with('/');
+
+        let mut segments = Vec::new();
+        for segment in relative.split('/') {
+            let segment = percent_decode(segment)?;
+            match segment.as_str() {
+                "" | "." => (),
+                ".." => {
+                    // outside of the root directory
+                    segments.pop()?;
+                }
+                _ => {
+                    // a single file name, `%2F` and the like don't make new segments
+                    let mut components = Path::new(&segment).components();
+                    match (components.next(), components.next()) {
+                        (Some(Component::Normal(_)), None) => segments.push(segment),
+                        _ => return None,
+                    }
+                }
+            }
+        }
+
+        let mut path = self.root.clone();
+        for segment in &segments {
+            path.push(segment);
+            if !self.follow_symlinks && is_symlink(&path) {
+                return None;
+            }
+        }
+
+        let metadata = fs::metadata(&path).ok()?;
+        if metadata.is_dir() {
+            if !trailing_slash {
+                // a path starting with `//` would be another host
+                let location = format!("/{}/", relative.trim_start_matches('/'));
+                return Some(Target::Redirect(location));
+            }
+            let index_file = self
+                .index_files
+                .iter()
+                .map(|index_file| path.join(index_file))
+                .find(|path| self.is_file(path));
+            match index_file {
+                Some(index_file) => Some(Target::File(index_file)),
+                None if self.listing => {
+                    let url_path: String = segments.iter().map(|s| format!("/{}", s)).collect();
+                    Some(Target::Listing(path, format!("{}/", url_path)))
+                }
+                None => None,
+            }
+        } else if metadata.is_file() && !trailing_slash {
+            Some(Target::File(path))
+        } else {
+            None
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        (self.follow_symlinks || !is_symlink(path)) && path.is_file()
+    }
+
+    /// Builds the response for a file, or for its compressed copy.
+    fn file_response(&self, path: &Path, request: &Request) -> Option<ResponseBox> {
+        let mut copies = Vec::new();
+        if self.precompressed {
+            for &(encoding, extension) in &[("br", "br"), ("gzip", "gz")] {
+                let mut copy = path.as_os_str().to_owned();
+                copy.push(".");
+                copy.push(extension);
+                let copy = PathBuf::from(copy);
+                if self.is_file(&copy) {
+                    copies.push((encoding, copy));
+                }
+            }
+        }
+
+        let accept_encoding = request
+            .headers()
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let compressed = choose_encoding(&accept_encoding, &copies)
+            .and_then(|(encoding, copy)| Some((*encoding, File::open(copy).ok()?)));
+
+        let mut response = match compressed {
+            Some((encoding, file)) => Response::from_file(file)
+                .with_header(header::CONTENT_ENCODING, HeaderValue::from_static(encoding)),
+            None => Response::from_file(File::open(path).ok()?),
+        };
+        response.add_header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(content_type(path)),
+        );
+        if !copies.is_empty() {
+            // the body depends on `Accept-Encoding`
+            response.add_header(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+        }
+        Some(response.boxed())
+    }
+}
+
+/// Picks the compressed copy with the highest `q` value in an `Accept-Encoding` header,
+/// the first one wins a tie.
+fn choose_encoding<'a>(
+    accept_encoding: &str,
+    copies: &'a [(&'static str, PathBuf)],
+) -> Option<&'a (&'static str, PathBuf)> {
+    let accepted = util::parse_header_value(accept_encoding);
+    let quality = |encoding: &str| {
+        accepted
+            .iter()
+            .find(|(coding, _)| {
+                coding.eq_ignore_ascii_case(encoding)
+                    || (encoding == "gzip" && coding.eq_ignore_ascii_case("x-gzip"))
+            })
+            .or_else(|| accepted.iter().find(|(coding, _)| *coding == "*"))
+            .map_or(0.0, |(_, q)| *q)
+    };
+
+    let mut best: Option<(&(&'static str, PathBuf), f32)> = None;
+    for copy in copies {
+        let q = quality(copy.0);
+        if q > best.map_or(0.0, |(_, best_q)| best_q) {
+            best = Some((copy, q));
+        }
+    }
+    best.map(|(copy, _)| copy)
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Decodes the `%XX` sequences of a segment of a URL path, which must give UTF-8.
+fn percent_decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut input = segment.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let high = (input.next()? as char).to_digit(16)?;
+            let low = (input.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Guesses the `Content-Type` of a file from its extension.
+fn content_type(path: &Path) -> &'static str {
+    let extension = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => extension.to_ascii_lowercase(),
+        None => return "application/octet-stream",
+    };
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "json" | "map" => "application/json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,328 @@
+//! Listings of the directories without an index file, see `StaticFiles::with_listing()`.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::{header, HeaderMap, HeaderValue};
+use httpdate::HttpDate;
+
+use super::{content_type, is_symlink, StaticFiles};
+use crate::util;
+use crate::{Request, Response, ResponseBox};
+
+/// An entry of a directory.
+struct Entry {
+    name: String,
+    directory: bool,
+    // `None` for the directories
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    kind: &'static str,
+}
+
+/// What the entries are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    Size,
+    Modified,
+    Type,
+}
+
+const SORT_KEYS: [SortKey; 4] = [
+    SortKey::Name,
+    SortKey::Size,
+    SortKey::Modified,
+    SortKey::Type,
+];
+
+impl SortKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Type => "type",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Size => "Size",
+            SortKey::Modified => "Modified",
+            SortKey::Type => "Type",
+        }
+    }
+}
+
+/// Reads the entries of a directory that can be listed.
+fn read_entries(files: &StaticFiles, directory: &Path) -> Option<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(directory).ok()? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        // the other names can't be requested
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if name.starts_with('.') && !files.hidden_files {
+            continue;
+        }
+
+        let path = entry.path();
+        if !files.follow_symlinks && is_symlink(&path) {
+            continue;
+        }
+        // follows the symbolic links, and skips the broken ones
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        let directory = metadata.is_dir();
+        let kind = if directory {
+            "directory"
+        } else {
+            content_type(&path).split(';').next().unwrap_or_default()
+        };
+        entries.push(Entry {
+            name,
+            directory,
+            size: if directory {
+                None
+            } else {
+                Some(metadata.len())
+            },
+            modified: metadata.modified().ok(),
+            kind,
+        });
+    }
+    Some(entries)
+}
+
+/// Sorts the entries, directories first.
+fn sort(entries: &mut [Entry], key: SortKey, descending: bool) {
+    entries.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Type => a.kind.cmp(b.kind),
+        }
+        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        b.directory.cmp(&a.directory).then(ordering)
+    });
+}
+
+/// Reads the `sort` and `order` query parameters.
+fn sort_order(query: Option<&str>) -> (SortKey, bool) {
+    let mut key = SortKey::Name;
+    let mut descending = false;
+    for (name, value) in query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match name {
+            "sort" => {
+                if let Some(found) = SORT_KEYS.iter().find(|key| key.as_str() == value) {
+                    key = *found;
+                }
+            }
+            "order" => descending = value == "desc",
+            _ => (),
+        }
+    }
+    (key, descending)
+}
+
+/// Whether the `Accept` header prefers JSON to HTML.
+fn wants_json(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    let accepted = util::parse_header_value(&accept);
+
+    // the most specific media range applies
+    let quality = |kind: &str, subtype: &str| {
+        let exact = format!("{}/{}", kind, subtype);
+        let any_subtype = format!("{}/*", kind);
+        [exact.as_str(), any_subtype.as_str(), "*/*"]
+            .iter()
+            .find_map(|range| {
+                accepted
+                    .iter()
+                    .find(|(media, _)| media.eq_ignore_ascii_case(range))
+            })
+            .map_or(0.0, |(_, q)| *q)
+    };
+    quality("application", "json") > quality("text", "html")
+}
+
+/// Builds the response listing a directory, whose normalized URL path is `url_path`.
+pub(super) fn response(
+    files: &StaticFiles,
+    directory: &Path,
+    url_path: &str,
+    request: &Request,
+) -> Option<ResponseBox> {
+    let mut entries = read_entries(files, directory)?;
+    let (key, descending) = sort_order(request.url().query());
+    sort(&mut entries, key, descending);
+
+    let (body, content_type) = if wants_json(request.headers()) {
+        (json(url_path, &entries), "application/json")
+    } else {
+        (
+            html(url_path, &entries, key, descending),
+            "text/html; charset=utf-8",
+        )
+    };
+    Some(
+        Response::from_string(body)
+            .with_header(header::CONTENT_TYPE, HeaderValue::from_static(content_type))
+            .with_header(header::VARY, HeaderValue::from_static("Accept"))
+            .boxed(),
+    )
+}
+
+fn html(url_path: &str, entries: &[Entry], key: SortKey, descending: bool) -> String {
+    let title = format!("Index of {}", escape_html(url_path));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         </head>\n<body>\n<h1>{}</h1>\n<table>\n<thead>\n<tr>",
+        title, title
+    );
+    for column in SORT_KEYS {
+        // sorting again by the same column reverses the order
+        let order = if column == key && !descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        let _ = write!(
+            html,
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>",
+            column.as_str(),
+            order,
+            column.title()
+        );
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+
+    if url_path != "/" {
+        html.push_str(
+            "<tr><td><a href=\"../\">../</a></td><td></td><td></td><td>directory</td></tr>\n",
+        );
+    }
+    for entry in entries {
+        let slash = if entry.directory { "/" } else { "" };
+        let size = entry.size.map(|size| size.to_string()).unwrap_or_default();
+        let modified = entry
+            .modified
+            .map(|modified| HttpDate::from(modified).to_string())
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            percent_encode(&entry.name),
+            slash,
+            escape_html(&entry.name),
+            slash,
+            size,
+            modified,
+            entry.kind
+        );
+    }
+
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+fn json(url_path: &str, entries: &[Entry]) -> String {
+    let mut json = format!("{{\"path\":{},\"entries\":[", escape_json(url_path));
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let size = entry
+            .size
+            .map_or_else(|| "null".to_owned(), |size| size.to_string());
+        let modified = entry
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or_else(|| "null".to_owned(), |since| since.as_secs().to_string());
+        let _ = write!(
+            json,
+            "{{\"name\":{},\"type\":{},\"size\":{},\"modified\":{}}}",
+            escape_json(&entry.name),
+            escape_json(entry.kind),
+            size,
+            modified
+        );
+    }
+    json.push_str("]}");
+    json
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quotes a JSON string.
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Encodes a file name as a segment of a URL path.
+fn percent_encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
@@ -0,0 +1,261 @@
+//! Responses whose body is written progressively, see `Request::respond_streaming()`.
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult, Write};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri};
+
+use crate::hooks::ResponseInfo;
+use crate::response::{
+    choose_transfer_encoding, date_header_value, write_message_header, TransferEncoding,
+};
+use crate::util::ConnectionGuard;
+use crate::Request;
+
+/// Amount of buffered data that is sent as a chunk without waiting for `flush()`.
+const CHUNK_SIZE: usize = 8192;
+
+/// How the end of the body is marked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// `Transfer-Encoding: chunked`, ended by an empty chunk and the trailers.
+    Chunked,
+    /// No length is sent, the connection is closed at the end of the body.
+    CloseDelimited,
+    /// The response has no body: HEAD requests, 1xx, 204 and 304 statuses.
+    NoBody,
+}
+
+/// Writes the body of a response progressively, returned by `Request::respond_streaming()`.
+///
+/// With HTTP/1.1 clients, the body is sent with the `chunked` transfer encoding. The data
+/// is buffered, and sent as one chunk whenever `flush()` is called or enough data has been
+/// written, so calling `flush()` after each part of the body sends it right away. With
+/// HTTP/1.0 clients, the data is sent as it is written, and the connection is closed at the
+/// end of the body.
+///
+/// The response ends when `finish()` is called or when the writer is dropped. Trailers set
+/// with `trailers_mut()` are sent after a chunked body, and ignored otherwise.
+///
+/// Errors are not hidden, so that a client that went away is noticed on the next write.
+pub struct StreamingWriter {
+    // `None` once the response has ended
+    writer: Option<Box<dyn Write + Send + 'static>>,
+    framing: Framing,
+    // true if the head said that the connection is closed after the response
+    close_connection: bool,
+    // data of the next chunk
+    buffer: Vec<u8>,
+    trailers: HeaderMap,
+
+    // what is needed to report the response to the hooks and release the connection
+    method: Method,
+    url: Uri,
+    status: StatusCode,
+    guard: Option<Arc<ConnectionGuard>>,
+    notify_when_finished: Option<Sender<()>>,
+}
+
+impl StreamingWriter {
+    /// Writes the head of the response to `writer`.
+    pub(crate) fn start(
+        writer: Box<dyn Write + Send + 'static>,
+        request: &Request,
+        status: StatusCode,
+        mut headers: HeaderMap,
+        guard: Option<Arc<ConnectionGuard>>,
+        notify_when_finished: Option<Sender<()>>,
+    ) -> IoResult<StreamingWriter> {
+        let framing = if status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+        {
+            Framing::NoBody
+        } else {
+            match choose_transfer_encoding(
+                status,
+                request.headers(),
+                request.http_version(),
+                &None,
+                false,
+                0,
+            ) {
+                TransferEncoding::Chunked => Framing::Chunked,
+                TransferEncoding::Identity => Framing::CloseDelimited,
+            }
+        };
+
+        // the framing is decided here
+        headers.remove(header::CONTENT_LENGTH);
+        headers.remove(header::TRANSFER_ENCODING);
+        headers.remove(header::CONNECTION);
+        match framing {
+            Framing::Chunked => {
+                headers.insert(
+                    header::TRANSFER_ENCODING,
+                    HeaderValue::from_static("chunked"),
+                );
+            }
+            Framing::CloseDelimited => {
+                headers.insert(header::CONNECTION, HeaderValue::from_static("close"));
+            }
+            Framing::NoBody => (),
+        }
+        if let header::Entry::Vacant(entry) = headers.entry(header::DATE) {
+            entry.insert(date_header_value());
+        }
+        if let header::Entry::Vacant(entry) = headers.entry(header::SERVER) {
+            entry.insert(HeaderValue::from_static("tiny-http (Rust)"));
+        }
+
+        let mut streaming = StreamingWriter {
+            writer: None,
+            framing,
+            close_connection: framing == Framing::CloseDelimited,
+            buffer: Vec::new(),
+            trailers: HeaderMap::new(),
+            method: request.method().clone(),
+            url: request.url().clone(),
+            status,
+            guard,
+            notify_when_finished,
+        };
+
+        let mut writer = writer;
+        let head = write_message_header(&mut writer, request.http_version(), &status, &headers)
+            .and_then(|()| writer.flush());
+        match head {
+            Ok(()) => {
+                if *request.method() == Method::HEAD {
+                    streaming.framing = Framing::NoBody;
+                }
+                streaming.writer = Some(writer);
+                Ok(streaming)
+            }
+            Err(err) => {
+                drop(writer);
+                streaming.complete(Some(&err));
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the trailers sent at the end of a chunked body.
+    ///
+    /// Clients only expect the trailers announced by a `Trailer` header in the head of the
+    /// response.
+    pub fn trailers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.trailers
+    }
+
+    /// Ends the response: sends the buffered data, then the end of the body and the trailers.
+    ///
+    /// Dropping the writer does the same, but ignores errors.
+    pub fn finish(mut self) -> IoResult<()> {
+        self.finish_impl()
+    }
+
+    fn finish_impl(&mut self) -> IoResult<()> {
+        let mut writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+
+        let result = (|| {
+            if self.framing == Framing::Chunked {
+                write_chunk(&mut writer, &self.buffer)?;
+                writer.write_all(b"0\r\n")?;
+                for (name, value) in self.trailers.iter() {
+                    writer.write_all(name.as_str().as_bytes())?;
+                    writer.write_all(b": ")?;
+                    writer.write_all(value.as_bytes())?;
+                    writer.write_all(b"\r\n")?;
+                }
+                writer.write_all(b"\r\n")?;
+            }
+            writer.flush()
+        })();
+        self.buffer.clear();
+        drop(writer);
+
+        self.complete(result.as_ref().err());
+        result
+    }
+
+    /// Reports the end of the response, and releases the connection.
+    fn complete(&mut self, error: Option<&IoError>) {
+        if let Some(guard) = self.guard.take() {
+            if let Some(hooks) = guard.hooks() {
+                hooks.response_completed(&ResponseInfo {
+                    connection: guard.info(),
+                    method: &self.method,
+                    url: &self.url,
+                    status: self.status,
+                    error,
+                });
+            }
+
+            // nothing else can be sent on the connection, even when a `HEAD` response had no body
+            if self.close_connection {
+                guard.close();
+            }
+        }
+
+        if let Some(sender) = self.notify_when_finished.take() {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl Write for StreamingWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let writer = match self.writer {
+            Some(ref mut writer) => writer,
+            None => return Err(IoError::new(IoErrorKind::NotConnected, "response finished")),
+        };
+
+        match self.framing {
+            Framing::Chunked => {
+                self.buffer.extend_from_slice(buf);
+                if self.buffer.len() >= CHUNK_SIZE {
+                    write_chunk(writer, &self.buffer)?;
+                    self.buffer.clear();
+                }
+                Ok(buf.len())
+            }
+            Framing::CloseDelimited => writer.write(buf),
+            Framing::NoBody => Ok(buf.len()),
+        }
+    }
+
+    /// Sends the data written so far, as one chunk with HTTP/1.1.
+    fn flush(&mut self) -> IoResult<()> {
+        let writer = match self.writer {
+            Some(ref mut writer) => writer,
+            None => return Ok(()),
+        };
+
+        if self.framing == Framing::Chunked {
+            write_chunk(writer, &self.buffer)?;
+            self.buffer.clear();
+        }
+        writer.flush()
+    }
+}
+
+impl Drop for StreamingWriter {
+    fn drop(&mut self) {
+        let _ = self.finish_impl();
+    }
+}
+
+/// Writes `data` as one chunk. Nothing is written for empty data, which would end the body.
+fn write_chunk<W: Write + ?Sized>(writer: &mut W, data: &[u8]) -> IoResult<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    write!(writer, "{:X}\r\n", data.len())?;
+    writer.write_all(data)?;
+    writer.write_all(b"\r\n")
+}
@@ -0,0 +1,111 @@
+//! Support for systemd socket activation.
+//!
+//! systemd passes the listening sockets as file descriptors starting at 3, and describes them
+//! with the `LISTEN_PID`, `LISTEN_FDS` and `LISTEN_FDNAMES` environment variables.
+//! See `sd_listen_fds(3)` for the details of the protocol.
+
+use std::env;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::util::fd;
+use crate::Listener;
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Name given by systemd to sockets without a `FileDescriptorName=`.
+const UNKNOWN_NAME: &str = "unknown";
+
+/// Whether the sockets passed by systemd have been claimed by `Listener::from_systemd()`.
+static CLAIMED: AtomicBool = AtomicBool::new(false);
+
+impl Listener {
+    /// Returns the listening sockets passed to this process by systemd socket activation.
+    ///
+    /// Every listener is returned along with its name, as set by `FileDescriptorName=` in the
+    /// socket unit, or `"unknown"` if systemd didn't provide one. If the process was not socket
+    /// activated, the list is empty.
+    ///
+    /// If one of the descriptors isn't a listening TCP or UNIX stream socket, an error is
+    /// returned and none of them is claimed.
+    ///
+    /// The sockets can only be claimed once: the next calls return an empty list. They are
+    /// marked close-on-exec, so child processes don't inherit them, and the activation
+    /// environment variables are left alone, see `clear_systemd_env()`.
+    ///
+    /// Note that `Server::from_listeners()` removes the socket files of UNIX listeners when the
+    /// server is dropped; use `Server::from_systemd()` to leave them to systemd.
+    pub fn from_systemd() -> IoResult<Vec<(String, Listener)>> {
+        let count = match listen_fds()? {
+            Some(count) => count,
+            None => return Ok(Vec::new()),
+        };
+        if CLAIMED.load(Ordering::SeqCst) {
+            return Ok(Vec::new());
+        }
+
+        // every socket is checked before any is owned, since dropping an owned socket would
+        // close it, and the claim can then be retried
+        let fds = LISTEN_FDS_START..LISTEN_FDS_START + count;
+        for fd in fds.clone() {
+            fd::inspect_inherited(fd, Listener::check_fd)?;
+        }
+        if CLAIMED.swap(true, Ordering::SeqCst) {
+            return Ok(Vec::new());
+        }
+
+        let names = env::var("LISTEN_FDNAMES").ok();
+        let mut names = names.iter().flat_map(|names| names.split(':'));
+        fds.map(|fd| {
+            let name = names.next().unwrap_or(UNKNOWN_NAME).to_owned();
+            Ok((name, Listener::from_owned_fd(fd::claim_inherited(fd)?)?))
+        })
+        .collect()
+    }
+
+    /// Removes the `LISTEN_PID`, `LISTEN_FDS` and `LISTEN_FDNAMES` environment variables set
+    /// by systemd socket activation.
+    ///
+    /// They are meant for this process only, and child processes ignore them since
+    /// `LISTEN_PID` doesn't match, so removing them is only a matter of hygiene.
+    ///
+    /// This must be called while the process has a single thread, typically at the start of
+    /// `main()` right after `from_systemd()`, since modifying the environment races with the
+    /// threads reading it (including the C library, for example to resolve host names).
+    pub fn clear_systemd_env() {
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_FDNAMES");
+    }
+}
+
+/// Returns the number of sockets passed by systemd, or `None` if they are not meant for
+/// this process.
+fn listen_fds() -> IoResult<Option<RawFd>> {
+    let invalid = |var: &str| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid {} environment variable", var),
+        )
+    };
+
+    let pid = match env::var("LISTEN_PID") {
+        Ok(pid) => pid.parse::<u32>().map_err(|_| invalid("LISTEN_PID"))?,
+        Err(_) => return Ok(None),
+    };
+    if pid != std::process::id() {
+        return Ok(None);
+    }
+
+    let count = match env::var("LISTEN_FDS") {
+        Ok(count) => count.parse::<RawFd>().map_err(|_| invalid("LISTEN_FDS"))?,
+        Err(_) => return Ok(None),
+    };
+    if count < 0 {
+        return Err(invalid("LISTEN_FDS"));
+    }
+
+    Ok(Some(count))
+}
@@ -1,6 +1,8 @@
-use crate::{request::new_request, Request};
-use http::{header, HeaderMap, HeaderName, HeaderValue, Method, Uri, Version};
+use crate::{request::new_request, ListenAddr, PeerCredentials, Request};
+use crate::{ArrivalTime, ConnectionId, TlsInfo};
+use http::{header, Extensions, HeaderMap, HeaderName, HeaderValue, Method, Uri, Version};
 use std::net::SocketAddr;
+use std::time::Instant;
 
 /// A simpler version of [`Request`] that is useful for testing. No data actually goes anywhere.
 ///
@@ -43,12 +45,15 @@ use std::net::SocketAddr;
 pub struct TestRequest {
     body: &'static str,
     remote_addr: SocketAddr,
+    local_addr: Option<ListenAddr>,
+    peer_credentials: Option<PeerCredentials>,
     // true if HTTPS, false if HTTP
     secure: bool,
     method: Method,
     path: Uri,
     http_version: Version,
     headers: HeaderMap,
+    extensions: Extensions,
 }
 
 impl From<TestRequest> for Request {
@@ -58,17 +63,31 @@ impl From<TestRequest> for Request {
         if let header::Entry::Vacant(vacant) = mock.headers.entry(header::CONTENT_TYPE) {
             vacant.insert(HeaderValue::from_str(&mock.body.len().to_string()).unwrap());
         }
-        new_request(
+        let mut request = new_request(
             mock.secure,
             mock.method,
             mock.path,
             mock.http_version,
             mock.headers,
             Some(mock.remote_addr),
+            mock.local_addr,
             mock.body.as_bytes(),
             std::io::sink(),
+            None,
         )
         .unwrap()
+        .with_peer_credentials(mock.peer_credentials);
+
+        // the built-in extensions, which can be replaced by the preset ones
+        let extensions = request.extensions_mut();
+        extensions.insert(ArrivalTime(Instant::now()));
+        extensions.insert(ConnectionId(0));
+        if mock.secure {
+            extensions.insert(TlsInfo::default());
+        }
+        extensions.extend(mock.extensions);
+
+        request
     }
 }
 
@@ -77,11 +96,14 @@ impl Default for TestRequest {
         TestRequest {
             body: "",
             remote_addr: "127.0.0.1:23456".parse().unwrap(),
+            local_addr: None,
+            peer_credentials: None,
             secure: false,
             method: Method::GET,
             path: Uri::default(),
             http_version: Version::HTTP_11,
             headers: HeaderMap::new(),
+            extensions: Extensions::new(),
         }
     }
 }
@@ -98,6 +120,14 @@ impl TestRequest {
         self.remote_addr = remote_addr;
         self
     }
+    pub fn with_local_addr<A: Into<ListenAddr>>(mut self, local_addr: A) -> Self {
+        self.local_addr = Some(local_addr.into());
+        self
+    }
+    pub fn with_peer_credentials(mut self, peer_credentials: PeerCredentials) -> Self {
+        self.peer_credentials = Some(peer_credentials);
+        self
+    }
     pub fn with_https(mut self) -> Self {
         self.secure = true;
         self
@@ -118,4 +148,15 @@ impl TestRequest {
         self.headers.append(name, value);
         self
     }
+    /// Adds a value to the extensions of the request.
+    ///
+    /// Like the server, `TestRequest` stores an `ArrivalTime`, a `ConnectionId` of `0` and,
+    /// for HTTPS requests, an empty `TlsInfo`. They can be replaced with this method.
+    pub fn with_extension<T>(mut self, value: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.extensions.insert(value);
+        self
+    }
 }
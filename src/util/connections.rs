@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::connection::Connection;
+use crate::hooks::{ConnectionInfo, ServerHooks};
+use crate::util::TaskPool;
+use crate::ConnectionSettings;
+
+/// Keeps track of the client connections that are open, so that the server can stop
+/// reading new requests from them.
+pub struct Connections {
+    next_id: AtomicUsize,
+    open: Mutex<HashMap<usize, OpenConnection>>,
+    // set once the server has stopped accepting connections
+    stopping: AtomicBool,
+    // called when the last connection closes after the server stopped
+    on_drained: Box<dyn Fn() + Send + Sync>,
+    // threads reading the requests of the connections
+    tasks_pool: TaskPool,
+}
+
+struct OpenConnection {
+    // used to interrupt a blocking read while the connection waits for a request
+    socket: Option<Connection>,
+    idle: Arc<AtomicBool>,
+}
+
+/// Registration of an open connection. The connection is forgotten when this is dropped.
+pub struct ConnectionGuard {
+    connections: Arc<Connections>,
+    idle: Arc<AtomicBool>,
+    // set once no other request must be read from this connection
+    closing: AtomicBool,
+    info: ConnectionInfo,
+    settings: Arc<ConnectionSettings>,
+}
+
+impl Connections {
+    /// Creates an empty list. The connections are served by the threads of `tasks_pool`.
+    /// `on_drained` is called once the server has stopped and every connection is closed.
+    pub fn new<F>(tasks_pool: TaskPool, on_drained: F) -> Arc<Connections>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::new(Connections {
+            next_id: AtomicUsize::new(0),
+            open: Mutex::new(HashMap::new()),
+            stopping: AtomicBool::new(false),
+            on_drained: Box::new(on_drained),
+            tasks_pool,
+        })
+    }
+
+    /// Runs a task on the threads that serve the connections.
+    pub fn spawn(&self, task: Box<dyn FnMut() + Send>) {
+        self.tasks_pool.spawn(task);
+    }
+
+    /// Registers a new connection. `socket` is a handle to the underlying socket, if
+    /// one could be obtained. The `id` of `info` is assigned here.
+    pub fn register(
+        self: &Arc<Self>,
+        socket: Option<Connection>,
+        mut info: ConnectionInfo,
+        settings: Arc<ConnectionSettings>,
+    ) -> ConnectionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        info.id = id;
+        let idle = Arc::new(AtomicBool::new(false));
+        self.open.lock().unwrap().insert(
+            id,
+            OpenConnection {
+                socket,
+                idle: idle.clone(),
+            },
+        );
+
+        if let Some(ref hooks) = settings.hooks {
+            hooks.connection_opened(&info);
+        }
+
+        ConnectionGuard {
+            connections: self.clone(),
+            idle,
+            closing: AtomicBool::new(false),
+            info,
+            settings,
+        }
+    }
+
+    /// Returns the number of open connections.
+    pub fn len(&self) -> usize {
+        self.open.lock().unwrap().len()
+    }
+
+    /// Returns `true` once the server has stopped and every connection is closed.
+    pub fn is_drained(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst) && self.len() == 0
+    }
+
+    /// Stops reading new requests. Connections that are waiting for a request are closed
+    /// immediately, the other ones are closed after their current request.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        let empty = {
+            let open = self.open.lock().unwrap();
+            for connection in open.values() {
+                if connection.idle.load(Ordering::SeqCst) {
+                    if let Some(socket) = &connection.socket {
+                        let _ = socket.shutdown(Shutdown::Read);
+                    }
+                }
+            }
+            open.is_empty()
+        };
+
+        if empty {
+            (self.on_drained)();
+        }
+    }
+}
+
+impl ConnectionGuard {
+    /// Marks the connection as waiting for the next request, or not.
+    ///
+    /// Returns `false` if the server is stopping or the connection is closing, in which case
+    /// no new request should be read.
+    pub fn set_idle(&self, idle: bool) -> bool {
+        self.idle.store(idle, Ordering::SeqCst);
+        !self.connections.stopping.load(Ordering::SeqCst) && !self.closing.load(Ordering::SeqCst)
+    }
+
+    /// Stops reading requests from this connection, interrupting a pending read.
+    pub fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+
+        if let Some(connection) = self.connections.open.lock().unwrap().get(&self.info.id) {
+            if let Some(socket) = &connection.socket {
+                let _ = socket.shutdown(Shutdown::Read);
+            }
+        }
+    }
+
+    /// Description of the connection.
+    pub fn info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+
+    /// Settings of the server that accepted the connection.
+    pub fn settings(&self) -> &ConnectionSettings {
+        &self.settings
+    }
+
+    /// Runs a task on the threads that serve the connections.
+    #[cfg(feature = "async")]
+    pub fn spawn(&self, task: Box<dyn FnMut() + Send>) {
+        self.connections.spawn(task);
+    }
+
+    /// Hooks of the server, if any.
+    pub fn hooks(&self) -> Option<&dyn ServerHooks> {
+        self.settings.hooks.as_deref()
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let empty = {
+            let mut open = self.connections.open.lock().unwrap();
+            open.remove(&self.info.id);
+            open.is_empty()
+        };
+        if let Some(hooks) = self.hooks() {
+            hooks.connection_closed(&self.info);
+        }
+        if empty && self.connections.stopping.load(Ordering::SeqCst) {
+            (self.connections.on_drained)();
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Taking ownership of raw file descriptors, which is the only `unsafe` code of the crate.
+
+#![allow(unsafe_code)]
+
+use std::io::{Error, Result as IoResult};
+use std::os::fd::{BorrowedFd, FromRawFd, OwnedFd, RawFd};
+
+use nix::libc;
+
+/// Calls `inspect` with a descriptor that this process inherited, after checking that it is
+/// open, without taking ownership of it.
+pub(crate) fn inspect_inherited<T>(
+    fd: RawFd,
+    inspect: impl FnOnce(BorrowedFd<'_>) -> IoResult<T>,
+) -> IoResult<T> {
+    // SAFETY: `F_GETFD` only reads the flags of the descriptor, and fails with `EBADF` if it
+    // isn't open
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(Error::last_os_error());
+    }
+
+    // SAFETY: the descriptor is open, and stays so during the call since nothing else in the
+    // process owns it, see `claim_inherited()`
+    inspect(unsafe { BorrowedFd::borrow_raw(fd) })
+}
+
+/// Takes ownership of a descriptor that this process inherited, after checking that it is
+/// open and marking it close-on-exec.
+///
+/// Nothing else in the process may own `fd`: systemd socket activation guarantees it with
+/// `LISTEN_PID`, and `Listener::from_systemd()` only claims its descriptors once.
+pub(crate) fn claim_inherited(fd: RawFd) -> IoResult<OwnedFd> {
+    // SAFETY: `F_GETFD` and `F_SETFD` only read and set the flags of the descriptor, and fail
+    // with `EBADF` if it isn't open
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } == -1 {
+        return Err(Error::last_os_error());
+    }
+
+    // SAFETY: the descriptor is open, and not owned by anything else according to the caller
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Takes ownership of the descriptors received in a `SCM_RIGHTS` control message.
+pub(crate) fn claim_received(fds: Vec<RawFd>) -> Vec<OwnedFd> {
+    fds.into_iter()
+        // SAFETY: the kernel just installed these descriptors in this process for this
+        // message, nothing else owns them
+        .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+        .collect()
+}
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 use std::sync::{Arc, Condvar, Mutex};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Waker};
 use std::time::{Duration, Instant};
 
 enum Control<T> {
@@ -13,6 +15,9 @@ where
 {
     queue: Mutex<VecDeque<Control<T>>>,
     condvar: Condvar,
+    // tasks waiting in `poll_pop()`, always locked after `queue`
+    #[cfg(feature = "async")]
+    wakers: Mutex<Vec<Waker>>,
 }
 
 impl<T> MessagesQueue<T>
@@ -23,6 +28,8 @@ where
         Arc::new(MessagesQueue {
             queue: Mutex::new(VecDeque::with_capacity(capacity)),
             condvar: Condvar::new(),
+            #[cfg(feature = "async")]
+            wakers: Mutex::new(Vec::new()),
         })
     }
 
@@ -31,6 +38,8 @@ where
         let mut queue = self.queue.lock().unwrap();
         queue.push_back(Control::Elem(value));
         self.condvar.notify_one();
+        drop(queue);
+        self.wake_tasks();
     }
 
     /// Unblock one thread stuck in pop loop.
@@ -38,8 +47,31 @@ where
         let mut queue = self.queue.lock().unwrap();
         queue.push_back(Control::Unblock);
         self.condvar.notify_one();
+        drop(queue);
+        self.wake_tasks();
     }
 
+    /// Wakes every thread stuck in `pop_unless()`, so that they check their condition again.
+    pub fn wake_all(&self) {
+        let queue = self.queue.lock().unwrap();
+        self.condvar.notify_all();
+        drop(queue);
+        self.wake_tasks();
+    }
+
+    /// Wakes every task that found the queue empty in `poll_pop()`. They compete with the
+    /// threads blocked in `pop()` for the new element, so all of them are woken.
+    #[cfg(feature = "async")]
+    fn wake_tasks(&self) {
+        let wakers = std::mem::take(&mut *self.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn wake_tasks(&self) {}
+
     /// Pops an element. Blocks until one is available.
     /// Returns None in case unblock() was issued.
     pub fn pop(&self) -> Option<T> {
@@ -56,6 +88,47 @@ where
         }
     }
 
+    /// Same as `pop()`, but also returns None once the queue is empty and `stop` returns true.
+    ///
+    /// `stop` is called with the queue locked, whenever the queue is empty and after every
+    /// `wake_all()`.
+    pub fn pop_unless<F>(&self, stop: F) -> Option<T>
+    where
+        F: Fn() -> bool,
+    {
+        let mut queue = self.queue.lock().unwrap();
+
+        loop {
+            match queue.pop_front() {
+                Some(Control::Elem(value)) => return Some(value),
+                Some(Control::Unblock) => return None,
+                None if stop() => return None,
+                None => (),
+            }
+
+            queue = self.condvar.wait(queue).unwrap();
+        }
+    }
+
+    /// Pops an element from async code. Returns `Pending` if the queue is empty, in which case
+    /// the task is woken by the next `push()`, `unblock()` or `wake_all()`.
+    /// Returns `Ready(None)` in case unblock() was issued.
+    #[cfg(feature = "async")]
+    pub fn poll_pop(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        match queue.pop_front() {
+            Some(Control::Elem(value)) => Poll::Ready(Some(value)),
+            Some(Control::Unblock) => Poll::Ready(None),
+            None => {
+                let mut wakers = self.wakers.lock().unwrap();
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+
     /// Tries to pop an element without blocking.
     pub fn try_pop(&self) -> Option<T> {
         let mut queue = self.queue.lock().unwrap();
@@ -1,16 +1,20 @@
+pub use self::connections::{ConnectionGuard, Connections};
 pub use self::custom_stream::CustomStream;
 pub use self::equal_reader::EqualReader;
 pub use self::fused_reader::FusedReader;
 pub use self::messages_queue::MessagesQueue;
 pub use self::refined_tcp_stream::RefinedTcpStream;
+pub use self::sequential::SequentialWriterBuilder;
 pub use self::sequential::{SequentialReader, SequentialReaderBuilder};
-pub use self::sequential::{SequentialWriter, SequentialWriterBuilder};
 pub use self::task_pool::TaskPool;
 
 use std::str::FromStr;
 
+mod connections;
 mod custom_stream;
 mod equal_reader;
+#[cfg(target_os = "linux")]
+pub(crate) mod fd;
 mod fused_reader;
 mod messages_queue;
 pub(crate) mod refined_tcp_stream;
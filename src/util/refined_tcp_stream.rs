@@ -2,7 +2,7 @@ use std::io::Result as IoResult;
 use std::io::{Read, Write};
 use std::net::{Shutdown, SocketAddr};
 
-use crate::connection::Connection;
+use crate::connection::{Connection, ListenAddr, PeerCredentials};
 #[cfg(any(
     feature = "ssl-openssl",
     feature = "ssl-rustls",
@@ -65,6 +65,30 @@ impl Stream {
         }
     }
 
+    fn peer_credentials(&mut self) -> IoResult<Option<PeerCredentials>> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.peer_credentials(),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.peer_credentials(),
+        }
+    }
+
+    fn local_addr(&mut self) -> IoResult<ListenAddr> {
+        match self {
+            Stream::Http(tcp_stream) => tcp_stream.local_addr(),
+            #[cfg(any(
+                feature = "ssl-openssl",
+                feature = "ssl-rustls",
+                feature = "ssl-native-tls"
+            ))]
+            Stream::Https(ssl_stream) => ssl_stream.local_addr(),
+        }
+    }
+
     fn shutdown(&mut self, how: Shutdown) -> IoResult<()> {
         match self {
             Stream::Http(tcp_stream) => tcp_stream.shutdown(how),
@@ -157,6 +181,14 @@ impl RefinedTcpStream {
     pub(crate) fn peer_addr(&mut self) -> IoResult<Option<SocketAddr>> {
         self.stream.peer_addr()
     }
+
+    pub(crate) fn local_addr(&mut self) -> IoResult<ListenAddr> {
+        self.stream.local_addr()
+    }
+
+    pub(crate) fn peer_credentials(&mut self) -> IoResult<Option<PeerCredentials>> {
+        self.stream.peer_credentials()
+    }
 }
 
 impl Drop for RefinedTcpStream {
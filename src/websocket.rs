@@ -0,0 +1,750 @@
+//! WebSocket connections (RFC 6455), opened with `Request::accept_websocket()`.
+//!
+//! This module is only available with the `websocket` feature. It works the same with plain
+//! and SSL listeners, since it only uses the stream returned by the upgrade.
+//!
+//! ```no_run
+//! use tiny_http::websocket::{Message, WebSocketConfig};
+//!
+//! # let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+//! let config = WebSocketConfig::default().with_protocols(["chat"]);
+//! for request in server.incoming_requests() {
+//!     if !request.is_websocket_upgrade() {
+//!         let _ = request.respond(tiny_http::Response::from_string("hello"));
+//!         continue;
+//!     }
+//!
+//!     let config = config.clone();
+//!     std::thread::spawn(move || {
+//!         let mut socket = match request.accept_websocket(&config) {
+//!             Ok(socket) => socket,
+//!             Err(_) => return, // a 400 response has been sent
+//!         };
+//!         while let Ok(message) = socket.recv() {
+//!             match message {
+//!                 Message::Text(text) => socket.send(Message::Text(text)).unwrap(),
+//!                 Message::Close(_) => break,
+//!                 _ => (),
+//!             }
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::convert::TryFrom;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Write};
+use std::sync::{Arc, Mutex};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version};
+
+use crate::{Request, Response};
+
+#[cfg(feature = "websocket-deflate")]
+mod deflate;
+#[cfg(feature = "websocket-deflate")]
+pub use self::deflate::DeflateConfig;
+
+/// Appended to the key sent by the client to compute `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+// close codes sent when the client doesn't follow the protocol
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Largest payload of a control frame.
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+impl Request {
+    /// Returns `true` if the client asks to upgrade the connection to a WebSocket.
+    ///
+    /// The rest of the handshake is checked by `accept_websocket()`.
+    pub fn is_websocket_upgrade(&self) -> bool {
+        has_token(self.headers(), header::UPGRADE, "websocket")
+    }
+
+    /// Completes the WebSocket handshake, and returns the connection.
+    ///
+    /// If the request isn't a valid WebSocket handshake, it is answered with a
+    /// `400 Bad Request` response, or `426 Upgrade Required` if the client uses another
+    /// version of the protocol, and an error of kind `InvalidData` is returned.
+    ///
+    /// The subprotocol is the first one of `config` that the client offers. If there is
+    /// none, the connection is opened without a subprotocol, and the client decides whether
+    /// to go on.
+    pub fn accept_websocket(self, config: &WebSocketConfig) -> IoResult<WebSocket> {
+        let accept = match check_handshake(&self) {
+            Ok(key) => accept_key(key),
+            Err(rejection) => {
+                let mut response = Response::empty(rejection.status);
+                if rejection.status == StatusCode::UPGRADE_REQUIRED {
+                    response.add_header(
+                        header::SEC_WEBSOCKET_VERSION,
+                        HeaderValue::from_static("13"),
+                    );
+                }
+                let _ = self.respond(response);
+                return Err(IoError::new(IoErrorKind::InvalidData, rejection.reason));
+            }
+        };
+
+        let offered = self
+            .headers()
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let protocol = config
+            .protocols
+            .iter()
+            .find(|protocol| offered.contains(&protocol.as_str()))
+            .cloned();
+
+        let mut response = Response::empty(StatusCode::SWITCHING_PROTOCOLS)
+            .with_header(header::SEC_WEBSOCKET_ACCEPT, accept);
+        if let Some(ref protocol) = protocol {
+            if let Ok(value) = HeaderValue::from_str(protocol) {
+                response.add_header(header::SEC_WEBSOCKET_PROTOCOL, value);
+            }
+        }
+
+        #[cfg(feature = "websocket-deflate")]
+        let deflate = config.deflate.as_ref().and_then(|deflate| {
+            let extensions = self.headers().get_all(header::SEC_WEBSOCKET_EXTENSIONS);
+            let negotiated = deflate::negotiate(deflate, extensions.iter())?;
+            response.add_header(header::SEC_WEBSOCKET_EXTENSIONS, negotiated.header_value());
+            Some((negotiated.compressor(deflate), negotiated.decompressor()))
+        });
+        #[cfg(feature = "websocket-deflate")]
+        let (compressor, decompressor) = match deflate {
+            Some((compressor, decompressor)) => (Some(compressor), Some(decompressor)),
+            None => (None, None),
+        };
+
+        let (reader, writer) = self.upgrade_parts("websocket", response);
+        let writer = WebSocketWriter {
+            state: Arc::new(Mutex::new(WriteState {
+                writer,
+                fragment_size: config.fragment_size,
+                #[cfg(feature = "websocket-deflate")]
+                compressor,
+                close_sent: false,
+            })),
+        };
+        Ok(WebSocket {
+            reader: WebSocketReader {
+                reader,
+                writer: writer.clone(),
+                max_frame_size: config.max_frame_size,
+                max_message_size: config.max_message_size,
+                #[cfg(feature = "websocket-deflate")]
+                decompressor,
+                partial: None,
+                closed: false,
+            },
+            writer,
+            protocol,
+        })
+    }
+}
+
+/// Status of the response to a request that isn't a valid handshake.
+struct Rejection {
+    status: StatusCode,
+    reason: &'static str,
+}
+
+/// Checks the handshake sent by the client, and returns its `Sec-WebSocket-Key`.
+fn check_handshake(request: &Request) -> Result<&str, Rejection> {
+    let bad_request = |reason| Rejection {
+        status: StatusCode::BAD_REQUEST,
+        reason,
+    };
+
+    if *request.method() != Method::GET || *request.http_version() < Version::HTTP_11 {
+        return Err(bad_request(
+            "WebSocket handshakes are HTTP/1.1 GET requests",
+        ));
+    }
+    if !request.is_websocket_upgrade()
+        || !has_token(request.headers(), header::CONNECTION, "upgrade")
+    {
+        return Err(bad_request("missing WebSocket upgrade headers"));
+    }
+
+    let key = request
+        .headers()
+        .get(header::SEC_WEBSOCKET_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| BASE64.decode(key).is_ok_and(|key| key.len() == 16))
+        .ok_or_else(|| bad_request("invalid Sec-WebSocket-Key"))?;
+
+    let version = request
+        .headers()
+        .get(header::SEC_WEBSOCKET_VERSION)
+        .and_then(|value| value.to_str().ok());
+    if version.map(str::trim) != Some("13") {
+        return Err(Rejection {
+            status: StatusCode::UPGRADE_REQUIRED,
+            reason: "unsupported WebSocket version",
+        });
+    }
+
+    Ok(key)
+}
+
+/// Computes the `Sec-WebSocket-Accept` value for a `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> HeaderValue {
+    let mut sha1 = sha1_smol::Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    let accept = BASE64.encode(sha1.digest().bytes());
+    HeaderValue::from_str(&accept).unwrap()
+}
+
+/// Returns `true` if one of the comma-separated values of the header is `token`.
+fn has_token(headers: &HeaderMap, name: HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Settings of the WebSocket connections opened by `Request::accept_websocket()`.
+#[derive(Debug, Clone)]
+pub struct WebSocketConfig {
+    protocols: Vec<String>,
+    max_frame_size: usize,
+    max_message_size: usize,
+    fragment_size: Option<usize>,
+    #[cfg(feature = "websocket-deflate")]
+    deflate: Option<DeflateConfig>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        WebSocketConfig {
+            protocols: Vec::new(),
+            max_frame_size: 16 << 20,
+            max_message_size: 64 << 20,
+            fragment_size: None,
+            #[cfg(feature = "websocket-deflate")]
+            deflate: None,
+        }
+    }
+}
+
+impl WebSocketConfig {
+    /// Sets the subprotocols supported by the server, by order of preference.
+    pub fn with_protocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the largest frame accepted from the client. Defaults to 16 MiB.
+    ///
+    /// The connection is closed with the code 1009 if the client sends a larger frame.
+    pub fn with_max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Sets the largest message accepted from the client, once its fragments are put
+    /// together. Defaults to 64 MiB.
+    ///
+    /// The connection is closed with the code 1009 if the client sends a larger message.
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Splits the messages sent to the client into frames of at most `size` bytes. By
+    /// default, each message is sent as a single frame.
+    pub fn with_fragment_size(mut self, size: usize) -> Self {
+        self.fragment_size = Some(size.max(1));
+        self
+    }
+
+    /// Compresses the messages with the `permessage-deflate` extension, if the client
+    /// offers it.
+    ///
+    /// Only available with the `websocket-deflate` feature.
+    #[cfg(feature = "websocket-deflate")]
+    pub fn with_deflate(mut self, deflate: DeflateConfig) -> Self {
+        self.deflate = Some(deflate);
+        self
+    }
+}
+
+/// A message received from or sent to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// A message of UTF-8 text.
+    Text(String),
+    /// A message of binary data.
+    Binary(Vec<u8>),
+    /// A ping. The pong is sent automatically when a ping is received.
+    Ping(Vec<u8>),
+    /// A pong, the answer to a ping.
+    Pong(Vec<u8>),
+    /// Starts or answers the closing handshake. The closing frame is answered automatically
+    /// when it is received.
+    Close(Option<CloseFrame>),
+}
+
+/// Code and reason of a closing frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// Status code, as defined in section 7.4 of RFC 6455. 1000 is a normal closure.
+    pub code: u16,
+    /// Reason of the closure, at most 123 bytes long.
+    pub reason: String,
+}
+
+/// A WebSocket connection, returned by `Request::accept_websocket()`.
+///
+/// Pings and closing frames from the client are answered automatically while receiving
+/// messages. A connection closed by the server with `close()` should keep receiving messages
+/// until the client answers with a `Message::Close`.
+///
+/// `split()` returns a reader and a writer, so that messages can be sent from other threads
+/// while one waits for the next message from the client.
+pub struct WebSocket {
+    reader: WebSocketReader,
+    writer: WebSocketWriter,
+    protocol: Option<String>,
+}
+
+impl WebSocket {
+    /// Returns the subprotocol chosen during the handshake.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Waits for the next message from the client. See `WebSocketReader::recv()`.
+    pub fn recv(&mut self) -> IoResult<Message> {
+        self.reader.recv()
+    }
+
+    /// Sends a message to the client. See `WebSocketWriter::send()`.
+    pub fn send(&mut self, message: Message) -> IoResult<()> {
+        self.writer.send(message)
+    }
+
+    /// Starts the closing handshake. See `WebSocketWriter::close()`.
+    pub fn close(&mut self, code: u16, reason: &str) -> IoResult<()> {
+        self.writer.close(code, reason)
+    }
+
+    /// Splits the connection into a reader and a writer.
+    pub fn split(self) -> (WebSocketReader, WebSocketWriter) {
+        (self.reader, self.writer)
+    }
+}
+
+/// Receiving half of a `WebSocket`.
+pub struct WebSocketReader {
+    reader: Box<dyn Read + Send>,
+    // used to answer pings and closing frames
+    writer: WebSocketWriter,
+    max_frame_size: usize,
+    max_message_size: usize,
+    #[cfg(feature = "websocket-deflate")]
+    decompressor: Option<deflate::Decompressor>,
+    // fragmented message being received
+    partial: Option<Partial>,
+    // set once nothing else can be received
+    closed: bool,
+}
+
+/// Why receiving a message failed.
+enum Failure {
+    Io(IoError),
+    /// The client broke the protocol, the connection is closed with this code.
+    Protocol(u16, &'static str),
+}
+
+impl From<IoError> for Failure {
+    fn from(err: IoError) -> Failure {
+        Failure::Io(err)
+    }
+}
+
+struct Frame {
+    fin: bool,
+    // set on the first frame of compressed messages
+    compressed: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+struct Partial {
+    opcode: u8,
+    #[cfg_attr(not(feature = "websocket-deflate"), allow(dead_code))]
+    compressed: bool,
+    data: Vec<u8>,
+}
+
+impl WebSocketReader {
+    /// Waits for the next message from the client.
+    ///
+    /// If the client breaks the protocol, for example with unmasked frames, invalid UTF-8 in
+    /// a text message or frames larger than the limits, the connection is closed with the
+    /// matching code and an error of kind `InvalidData` is returned. Once a
+    /// `Message::Close` has been received, or after an error, an error of kind
+    /// `NotConnected` is returned.
+    pub fn recv(&mut self) -> IoResult<Message> {
+        if self.closed {
+            return Err(closed_error());
+        }
+
+        match self.next_message() {
+            Ok(message) => {
+                if let Message::Close(_) = message {
+                    self.closed = true;
+                }
+                Ok(message)
+            }
+            Err(Failure::Io(err)) => {
+                self.closed = true;
+                Err(err)
+            }
+            Err(Failure::Protocol(code, reason)) => {
+                self.closed = true;
+                let _ = self.writer.close(code, reason);
+                Err(IoError::new(IoErrorKind::InvalidData, reason))
+            }
+        }
+    }
+
+    fn next_message(&mut self) -> Result<Message, Failure> {
+        loop {
+            let frame = self.read_frame()?;
+            match frame.opcode {
+                OP_TEXT | OP_BINARY => {
+                    if self.partial.is_some() {
+                        return Err(Failure::Protocol(
+                            CLOSE_PROTOCOL_ERROR,
+                            "new message before the end of a fragmented one",
+                        ));
+                    }
+                    let partial = Partial {
+                        opcode: frame.opcode,
+                        compressed: frame.compressed,
+                        data: frame.payload,
+                    };
+                    if frame.fin {
+                        return self.data_message(partial);
+                    }
+                    self.partial = Some(partial);
+                }
+                OP_CONTINUATION => {
+                    let mut partial = self.partial.take().ok_or(Failure::Protocol(
+                        CLOSE_PROTOCOL_ERROR,
+                        "continuation frame outside of a fragmented message",
+                    ))?;
+                    partial.data.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        return self.data_message(partial);
+                    }
+                    self.partial = Some(partial);
+                }
+                OP_PING => {
+                    self.writer.pong(&frame.payload)?;
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OP_PONG => return Ok(Message::Pong(frame.payload)),
+                _ => {
+                    let close = parse_close(&frame.payload)?;
+                    self.writer.answer_close(close.as_ref())?;
+                    return Ok(Message::Close(close));
+                }
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, Failure> {
+        let mut head = [0; 2];
+        self.reader.read_exact(&mut head)?;
+
+        let fin = head[0] & 0x80 != 0;
+        let compressed = head[0] & 0x40 != 0;
+        let opcode = head[0] & 0x0F;
+        if head[0] & 0x30 != 0 || (compressed && !self.accepts_compressed(opcode)) {
+            return Err(Failure::Protocol(CLOSE_PROTOCOL_ERROR, "reserved bits set"));
+        }
+        if !matches!(
+            opcode,
+            OP_CONTINUATION | OP_TEXT | OP_BINARY | OP_CLOSE | OP_PING | OP_PONG
+        ) {
+            return Err(Failure::Protocol(CLOSE_PROTOCOL_ERROR, "unknown opcode"));
+        }
+        if head[1] & 0x80 == 0 {
+            return Err(Failure::Protocol(
+                CLOSE_PROTOCOL_ERROR,
+                "unmasked frame from the client",
+            ));
+        }
+
+        let length = match head[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                self.reader.read_exact(&mut length)?;
+                u64::from(u16::from_be_bytes(length))
+            }
+            127 => {
+                let mut length = [0; 8];
+                self.reader.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => u64::from(length),
+        };
+
+        let is_control = opcode >= OP_CLOSE;
+        if is_control && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+            return Err(Failure::Protocol(
+                CLOSE_PROTOCOL_ERROR,
+                "fragmented or too long control frame",
+            ));
+        }
+        let length = match usize::try_from(length) {
+            Ok(length) if length <= self.max_frame_size => length,
+            _ => return Err(Failure::Protocol(CLOSE_TOO_BIG, "frame too big")),
+        };
+        let buffered = self
+            .partial
+            .as_ref()
+            .map_or(0, |partial| partial.data.len());
+        if !is_control && buffered + length > self.max_message_size {
+            return Err(Failure::Protocol(CLOSE_TOO_BIG, "message too big"));
+        }
+
+        let mut mask = [0; 4];
+        self.reader.read_exact(&mut mask)?;
+        let mut payload = vec![0; length];
+        self.reader.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Frame {
+            fin,
+            compressed,
+            opcode,
+            payload,
+        })
+    }
+
+    /// Returns `true` if the frame may have the bit of compressed messages set.
+    #[cfg(feature = "websocket-deflate")]
+    fn accepts_compressed(&self, opcode: u8) -> bool {
+        self.decompressor.is_some() && (opcode == OP_TEXT || opcode == OP_BINARY)
+    }
+
+    #[cfg(not(feature = "websocket-deflate"))]
+    fn accepts_compressed(&self, _opcode: u8) -> bool {
+        false
+    }
+
+    fn data_message(&mut self, partial: Partial) -> Result<Message, Failure> {
+        #[cfg(feature = "websocket-deflate")]
+        let data = match self.decompressor {
+            Some(ref mut decompressor) if partial.compressed => {
+                decompressor.decompress(partial.data, self.max_message_size)?
+            }
+            _ => partial.data,
+        };
+        #[cfg(not(feature = "websocket-deflate"))]
+        let data = partial.data;
+
+        if partial.opcode == OP_BINARY {
+            return Ok(Message::Binary(data));
+        }
+        String::from_utf8(data)
+            .map(Message::Text)
+            .map_err(|_| Failure::Protocol(CLOSE_INVALID_DATA, "invalid UTF-8 in a text message"))
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<Option<CloseFrame>, Failure> {
+    if payload.is_empty() {
+        return Ok(None);
+    }
+    if payload.len() < 2 {
+        return Err(Failure::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "invalid closing frame",
+        ));
+    }
+
+    // codes that may be sent, see the IANA registry
+    let code = u16::from_be_bytes([payload[0], payload[1]]);
+    if !matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999) {
+        return Err(Failure::Protocol(
+            CLOSE_PROTOCOL_ERROR,
+            "invalid close code",
+        ));
+    }
+    let reason = String::from_utf8(payload[2..].to_vec())
+        .map_err(|_| Failure::Protocol(CLOSE_INVALID_DATA, "invalid UTF-8 in a closing frame"))?;
+
+    Ok(Some(CloseFrame { code, reason }))
+}
+
+fn closed_error() -> IoError {
+    IoError::new(IoErrorKind::NotConnected, "WebSocket closed")
+}
+
+/// Sending half of a `WebSocket`. Clones send on the same connection.
+#[derive(Clone)]
+pub struct WebSocketWriter {
+    state: Arc<Mutex<WriteState>>,
+}
+
+struct WriteState {
+    writer: Box<dyn Write + Send>,
+    fragment_size: Option<usize>,
+    #[cfg(feature = "websocket-deflate")]
+    compressor: Option<deflate::Compressor>,
+    // set once nothing else can be sent
+    close_sent: bool,
+}
+
+impl WebSocketWriter {
+    /// Sends a message to the client.
+    ///
+    /// Sending a `Message::Close` starts the closing handshake, after which nothing else can
+    /// be sent. The payload of pings and pongs is limited to 125 bytes.
+    pub fn send(&self, message: Message) -> IoResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.close_sent {
+            return Err(closed_error());
+        }
+
+        match message {
+            Message::Text(text) => state.write_message(OP_TEXT, text.as_bytes()),
+            Message::Binary(data) => state.write_message(OP_BINARY, &data),
+            Message::Ping(data) => state.write_control(OP_PING, &data),
+            Message::Pong(data) => state.write_control(OP_PONG, &data),
+            Message::Close(frame) => state.write_close(frame.as_ref()),
+        }
+    }
+
+    /// Starts the closing handshake with a status code and a reason.
+    pub fn close(&self, code: u16, reason: &str) -> IoResult<()> {
+        self.send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_owned(),
+        })))
+    }
+
+    fn pong(&self, data: &[u8]) -> IoResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.close_sent {
+            return Ok(());
+        }
+        state.write_control(OP_PONG, data)
+    }
+
+    /// Answers a closing frame from the client, unless the server started the handshake.
+    fn answer_close(&self, frame: Option<&CloseFrame>) -> IoResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.close_sent {
+            return Ok(());
+        }
+        let answer = frame.map(|frame| CloseFrame {
+            code: frame.code,
+            reason: String::new(),
+        });
+        state.write_close(answer.as_ref())
+    }
+}
+
+impl WriteState {
+    fn write_message(&mut self, opcode: u8, data: &[u8]) -> IoResult<()> {
+        let mut opcode = opcode;
+
+        // the first frame of compressed messages has the RSV1 bit set
+        #[cfg(feature = "websocket-deflate")]
+        let compressed = match self.compressor {
+            Some(ref mut compressor) => {
+                opcode |= 0x40;
+                Some(compressor.compress(data)?)
+            }
+            None => None,
+        };
+        #[cfg(feature = "websocket-deflate")]
+        let data = compressed.as_deref().unwrap_or(data);
+
+        let fragment_size = self.fragment_size.unwrap_or(usize::MAX);
+        let mut remaining = data;
+        loop {
+            let (fragment, rest) = remaining.split_at(remaining.len().min(fragment_size));
+            self.write_frame(rest.is_empty(), opcode, fragment)?;
+            if rest.is_empty() {
+                break;
+            }
+            opcode = OP_CONTINUATION;
+            remaining = rest;
+        }
+        self.writer.flush()
+    }
+
+    fn write_control(&mut self, opcode: u8, data: &[u8]) -> IoResult<()> {
+        if data.len() > MAX_CONTROL_PAYLOAD {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "control frame payload longer than 125 bytes",
+            ));
+        }
+        self.write_frame(true, opcode, data)?;
+        self.writer.flush()
+    }
+
+    fn write_close(&mut self, frame: Option<&CloseFrame>) -> IoResult<()> {
+        let mut payload = Vec::new();
+        if let Some(frame) = frame {
+            payload.extend_from_slice(&frame.code.to_be_bytes());
+            payload.extend_from_slice(frame.reason.as_bytes());
+        }
+        self.write_control(OP_CLOSE, &payload)?;
+        self.close_sent = true;
+        Ok(())
+    }
+
+    fn write_frame(&mut self, fin: bool, opcode: u8, payload: &[u8]) -> IoResult<()> {
+        let mut head = Vec::with_capacity(10);
+        head.push(if fin { 0x80 } else { 0 } | opcode);
+        match payload.len() {
+            length @ 0..=125 => head.push(length as u8),
+            length @ 126..=0xFFFF => {
+                head.push(126);
+                head.extend_from_slice(&(length as u16).to_be_bytes());
+            }
+            length => {
+                head.push(127);
+                head.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+        self.writer.write_all(&head)?;
+        self.writer.write_all(payload)
+    }
+}
@@ -0,0 +1,304 @@
+//! The `permessage-deflate` extension (RFC 7692), enabled by the `websocket-deflate` feature.
+
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Result as IoResult};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use http::HeaderValue;
+
+use super::{Failure, CLOSE_INVALID_DATA, CLOSE_TOO_BIG};
+
+/// Ends the data of each message, and is removed from the messages that are sent.
+const SYNC_FLUSH_TAIL: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+/// Smallest window the server can compress with: zlib can't use 8 bits, and miniz_oxide, the
+/// default backend of `flate2`, only uses 15.
+const MIN_SERVER_WINDOW_BITS: u8 = if cfg!(feature = "zlib-rs") { 9 } else { 15 };
+
+/// Settings of the `permessage-deflate` extension, see `WebSocketConfig::with_deflate()`.
+///
+/// The extension is used if the client offers it. Compressed messages from the client are
+/// limited by the maximum message size of the connection once decompressed, and the
+/// decompression stops as soon as the limit is reached.
+#[derive(Debug, Clone)]
+pub struct DeflateConfig {
+    level: u32,
+    server_max_window_bits: u8,
+    client_max_window_bits: Option<u8>,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        DeflateConfig {
+            level: 6,
+            server_max_window_bits: 15,
+            client_max_window_bits: None,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+        }
+    }
+}
+
+impl DeflateConfig {
+    /// Sets the compression level, from 0 to 9. Defaults to 6.
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Limits the window used to compress the messages sent by the server, from 9 to 15 bits.
+    /// Smaller windows use less memory on both sides, and compress less. Defaults to 15.
+    ///
+    /// Windows smaller than 15 bits need the `zlib-rs` feature, and are ignored without it.
+    pub fn with_server_max_window_bits(mut self, bits: u8) -> Self {
+        self.server_max_window_bits = bits.clamp(9, 15);
+        self
+    }
+
+    /// Asks the clients that allow it to compress with a window of at most `bits`, from 8
+    /// to 15 bits.
+    pub fn with_client_max_window_bits(mut self, bits: u8) -> Self {
+        self.client_max_window_bits = Some(bits.clamp(8, 15));
+        self
+    }
+
+    /// Compresses each message sent by the server on its own, which saves the memory kept
+    /// between messages at the cost of a worse compression.
+    pub fn with_server_no_context_takeover(mut self) -> Self {
+        self.server_no_context_takeover = true;
+        self
+    }
+
+    /// Asks the clients to compress each message on their own.
+    pub fn with_client_no_context_takeover(mut self) -> Self {
+        self.client_no_context_takeover = true;
+        self
+    }
+}
+
+/// Parameters agreed with the client.
+#[derive(Debug)]
+pub(crate) struct Negotiated {
+    server_max_window_bits: u8,
+    server_max_window_bits_sent: bool,
+    client_max_window_bits: Option<u8>,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl Negotiated {
+    /// Value of the `Sec-WebSocket-Extensions` header of the response.
+    pub(crate) fn header_value(&self) -> HeaderValue {
+        let mut value = String::from("permessage-deflate");
+        if self.server_no_context_takeover {
+            value.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; client_no_context_takeover");
+        }
+        if self.server_max_window_bits_sent {
+            value.push_str(&format!(
+                "; server_max_window_bits={}",
+                self.server_max_window_bits
+            ));
+        }
+        if let Some(bits) = self.client_max_window_bits {
+            value.push_str(&format!("; client_max_window_bits={}", bits));
+        }
+        HeaderValue::from_str(&value).unwrap()
+    }
+
+    pub(crate) fn compressor(&self, config: &DeflateConfig) -> Compressor {
+        #[cfg(feature = "zlib-rs")]
+        let compress = Compress::new_with_window_bits(
+            Compression::new(config.level),
+            false,
+            self.server_max_window_bits,
+        );
+        #[cfg(not(feature = "zlib-rs"))]
+        let compress = Compress::new(Compression::new(config.level), false);
+
+        Compressor {
+            compress,
+            no_context_takeover: self.server_no_context_takeover,
+        }
+    }
+
+    pub(crate) fn decompressor(&self) -> Decompressor {
+        Decompressor {
+            // a full window can decompress the data of any smaller window
+            decompress: Decompress::new(false),
+            no_context_takeover: self.client_no_context_takeover,
+        }
+    }
+}
+
+/// Picks the first offer of `permessage-deflate` in the `Sec-WebSocket-Extensions` headers
+/// that can be accepted.
+pub(crate) fn negotiate<'a, I>(config: &DeflateConfig, headers: I) -> Option<Negotiated>
+where
+    I: Iterator<Item = &'a HeaderValue>,
+{
+    headers
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|offer| {
+            let mut params = offer.split(';').map(str::trim);
+            if params.next() != Some("permessage-deflate") {
+                return None;
+            }
+            accept_offer(config, params)
+        })
+        .next()
+}
+
+fn accept_offer<'a, I>(config: &DeflateConfig, params: I) -> Option<Negotiated>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut server_no_context_takeover = false;
+    let mut client_no_context_takeover = false;
+    let mut server_max_window_bits = None;
+    let mut client_max_window_bits = None;
+
+    for param in params {
+        let (name, value) = match param.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (param, None),
+        };
+        let window_bits = |value: Option<&str>| {
+            value
+                .filter(|value| value.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|value| value.parse::<u8>().ok())
+                .filter(|bits| (8..=15).contains(bits))
+        };
+
+        // unknown, repeated or invalid parameters make the offer unacceptable
+        match name {
+            "server_no_context_takeover" if value.is_none() && !server_no_context_takeover => {
+                server_no_context_takeover = true
+            }
+            "client_no_context_takeover" if value.is_none() && !client_no_context_takeover => {
+                client_no_context_takeover = true
+            }
+            "server_max_window_bits" if server_max_window_bits.is_none() => {
+                server_max_window_bits = Some(window_bits(value)?)
+            }
+            "client_max_window_bits" if client_max_window_bits.is_none() => {
+                client_max_window_bits = Some(match value {
+                    Some(_) => Some(window_bits(value)?),
+                    None => None,
+                })
+            }
+            _ => return None,
+        }
+    }
+
+    if server_max_window_bits.is_some_and(|bits| bits < MIN_SERVER_WINDOW_BITS) {
+        return None;
+    }
+    let config_window_bits = config.server_max_window_bits.max(MIN_SERVER_WINDOW_BITS);
+
+    Some(Negotiated {
+        server_max_window_bits: server_max_window_bits.unwrap_or(15).min(config_window_bits),
+        server_max_window_bits_sent: server_max_window_bits.is_some() || config_window_bits < 15,
+        // only sent to clients that allow it
+        client_max_window_bits: match (client_max_window_bits, config.client_max_window_bits) {
+            (Some(offered), Some(bits)) => Some(offered.unwrap_or(15).min(bits)),
+            _ => None,
+        },
+        server_no_context_takeover: server_no_context_takeover || config.server_no_context_takeover,
+        client_no_context_takeover: client_no_context_takeover || config.client_no_context_takeover,
+    })
+}
+
+/// Compresses the messages sent by the server.
+pub(crate) struct Compressor {
+    compress: Compress,
+    no_context_takeover: bool,
+}
+
+impl Compressor {
+    pub(crate) fn compress(&mut self, data: &[u8]) -> IoResult<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut output, FlushCompress::Sync)
+                .map_err(|err| IoError::new(IoErrorKind::Other, err))?;
+
+            // done once all the input is used and the output wasn't full
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == data.len() && output.len() < output.capacity() {
+                break;
+            }
+            output.reserve(output.capacity());
+        }
+
+        if output.ends_with(&SYNC_FLUSH_TAIL) {
+            output.truncate(output.len() - SYNC_FLUSH_TAIL.len());
+        }
+        if self.no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(output)
+    }
+}
+
+/// Decompresses the messages sent by the client.
+pub(crate) struct Decompressor {
+    decompress: Decompress,
+    no_context_takeover: bool,
+}
+
+impl Decompressor {
+    /// Decompresses a message, failing once more than `limit` bytes are produced.
+    pub(crate) fn decompress(
+        &mut self,
+        mut data: Vec<u8>,
+        limit: usize,
+    ) -> Result<Vec<u8>, Failure> {
+        data.extend_from_slice(&SYNC_FLUSH_TAIL);
+
+        let mut output = Vec::with_capacity(data.len().saturating_mul(2).min(limit) + 1);
+        let start = self.decompress.total_in();
+        let invalid = || Failure::Protocol(CLOSE_INVALID_DATA, "invalid compressed message");
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let produced = output.len();
+            let status = self
+                .decompress
+                .decompress_vec(&data[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|_| invalid())?;
+            if output.len() > limit {
+                return Err(Failure::Protocol(CLOSE_TOO_BIG, "message too big"));
+            }
+
+            // the client ended the compressed stream, the next message starts a new one
+            if status == Status::StreamEnd {
+                self.decompress.reset(false);
+                return Ok(output);
+            }
+
+            let done = (self.decompress.total_in() - start) as usize;
+            if output.len() < output.capacity() {
+                if done == data.len() {
+                    break;
+                }
+                if done == consumed && output.len() == produced {
+                    return Err(invalid());
+                }
+            }
+            // never more than one byte over the limit
+            let room = output.capacity().min(limit + 1 - output.len()).max(1);
+            output.reserve(room);
+        }
+
+        if self.no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(output)
+    }
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "async")]
+
+extern crate tiny_http;
+
+use std::future::poll_fn;
+use std::io::{Read, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_executor::block_on;
+
+#[allow(dead_code)]
+mod support;
+
+#[test]
+fn recv_and_respond_async() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    let client = thread::spawn(move || {
+        // the request arrives while the future is pending
+        thread::sleep(Duration::from_millis(100));
+        write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        content
+    });
+
+    block_on(async {
+        let request = server.recv_async().await.unwrap();
+        let response = tiny_http::Response::from_string("hello world");
+        request.respond_async(response).await.unwrap();
+    });
+
+    assert!(client.join().unwrap().ends_with("hello world"));
+}
+
+#[test]
+fn incoming_async_ends_on_unblock() {
+    let server = Arc::new(tiny_http::Server::http("0.0.0.0:0").unwrap());
+
+    let s = server.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        s.unblock();
+    });
+
+    let mut incoming = server.incoming_async();
+    let next = block_on(poll_fn(|cx| Pin::new(&mut incoming).poll_next(cx)));
+    assert!(next.is_none());
+}
+
+#[test]
+fn respond_async_to_test_request() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    let response = tiny_http::Response::from_string("hello world");
+    block_on(request.respond_async(response)).unwrap();
+}
@@ -0,0 +1,390 @@
+#![cfg(feature = "compression")]
+
+extern crate tiny_http;
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use http::{header, HeaderValue, StatusCode};
+use tiny_http::{CompressionConfig, ContentEncoding, DecompressionConfig, Response, ResponseBox};
+
+#[allow(dead_code)]
+mod support;
+
+/// Sends a request with the given `Accept-Encoding` to a server with compression enabled,
+/// and returns the head of the response and its body, without the chunks.
+fn exchange(
+    config: CompressionConfig,
+    http_version: &str,
+    accept_encoding: Option<&str>,
+    response: ResponseBox,
+) -> (String, Vec<u8>) {
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        compression: Some(config),
+        ..Default::default()
+    })
+    .unwrap();
+    let mut client = TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+
+    write!(client, "GET / {}\r\nHost: localhost\r\n", http_version).unwrap();
+    if let Some(accept_encoding) = accept_encoding {
+        write!(client, "Accept-Encoding: {}\r\n", accept_encoding).unwrap();
+    }
+    write!(client, "Connection: close\r\n\r\n").unwrap();
+
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    let (head, mut body) = support::split_response(&content);
+    if head.contains("transfer-encoding: chunked\r\n") {
+        let mut decoded = Vec::new();
+        chunked_transfer::Decoder::new(&body[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        body = decoded;
+    }
+    (head, body)
+}
+
+fn text_body(len: usize) -> String {
+    "compressible text ".repeat(len / 18 + 1)[..len].to_owned()
+}
+
+fn text(len: usize) -> ResponseBox {
+    Response::from_string(text_body(len)).boxed()
+}
+
+fn decode(encoding: &str, body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    match encoding {
+        "gzip" => flate2::read::GzDecoder::new(body)
+            .read_to_end(&mut decoded)
+            .unwrap(),
+        "deflate" => flate2::read::ZlibDecoder::new(body)
+            .read_to_end(&mut decoded)
+            .unwrap(),
+        "br" => brotli::Decompressor::new(body, 4096)
+            .read_to_end(&mut decoded)
+            .unwrap(),
+        "zstd" => zstd::stream::read::Decoder::new(body)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap(),
+        _ => unreachable!(),
+    };
+    decoded
+}
+
+#[test]
+fn every_encoding() {
+    for encoding in ["gzip", "deflate", "br", "zstd"] {
+        let (head, body) = exchange(
+            CompressionConfig::default(),
+            "HTTP/1.1",
+            Some(encoding),
+            text(5000),
+        );
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("content-encoding: {}\r\n", encoding)));
+        assert!(head.contains("vary: Accept-Encoding\r\n"));
+        assert!(head.contains("transfer-encoding: chunked\r\n"));
+        assert!(!head.contains("content-length"));
+        assert!(body.len() < 1000);
+        assert_eq!(decode(encoding, &body), text_body(5000).as_bytes());
+    }
+}
+
+#[test]
+fn negotiation() {
+    let chosen = |config: CompressionConfig, accept_encoding: Option<&str>| {
+        let (head, _) = exchange(config, "HTTP/1.1", accept_encoding, text(5000));
+        assert!(head.contains("vary: Accept-Encoding\r\n"));
+        head.lines()
+            .find_map(|line| line.strip_prefix("content-encoding: "))
+            .map(str::to_owned)
+    };
+    let default = CompressionConfig::default;
+
+    assert_eq!(chosen(default(), Some("gzip, br")).as_deref(), Some("br"));
+    assert_eq!(
+        chosen(default(), Some("gzip;q=1.0, br;q=0.5")).as_deref(),
+        Some("gzip")
+    );
+    assert_eq!(
+        chosen(default(), Some("br;q=0, *;q=0.5, zstd;q=0")).as_deref(),
+        Some("gzip")
+    );
+    assert_eq!(chosen(default(), Some("x-gzip")).as_deref(), Some("gzip"));
+    assert_eq!(chosen(default(), Some("identity")), None);
+    assert_eq!(chosen(default(), Some("")), None);
+    assert_eq!(chosen(default(), None), None);
+
+    let gzip_only = || default().with_encodings([ContentEncoding::Gzip]);
+    assert_eq!(chosen(gzip_only(), Some("br, zstd")), None);
+    assert_eq!(
+        chosen(gzip_only(), Some("br, gzip;q=0.1")).as_deref(),
+        Some("gzip")
+    );
+}
+
+#[test]
+fn not_compressed() {
+    let check = |response: ResponseBox, vary: bool| {
+        let (head, _) = exchange(
+            CompressionConfig::default().with_min_size(100),
+            "HTTP/1.1",
+            Some("gzip"),
+            response,
+        );
+        assert!(!head.contains("content-encoding: gzip"), "{}", head);
+        assert_eq!(head.contains("vary: Accept-Encoding\r\n"), vary, "{}", head);
+    };
+
+    // too small, unknown or incompressible types
+    check(text(99), false);
+    check(
+        Response::from_data(vec![0; 5000])
+            .with_header(header::CONTENT_TYPE, HeaderValue::from_static("image/png"))
+            .boxed(),
+        false,
+    );
+    check(Response::from_data(vec![0; 5000]).boxed(), false);
+
+    // already encoded, or not to be transformed
+    check(
+        text(5000)
+            .with_header(header::CONTENT_ENCODING, HeaderValue::from_static("br"))
+            .boxed(),
+        false,
+    );
+    check(
+        text(5000)
+            .with_header(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("no-transform"),
+            )
+            .boxed(),
+        false,
+    );
+    check(
+        text(5000)
+            .with_status_code(StatusCode::PARTIAL_CONTENT)
+            .boxed(),
+        false,
+    );
+    check(Response::empty(StatusCode::NO_CONTENT).boxed(), false);
+}
+
+#[test]
+fn existing_headers() {
+    let (head, body) = exchange(
+        CompressionConfig::default(),
+        "HTTP/1.1",
+        Some("gzip"),
+        text(5000)
+            .with_header(header::VARY, HeaderValue::from_static("accept-encoding"))
+            .with_header(header::ETAG, HeaderValue::from_static("\"abc\""))
+            .boxed(),
+    );
+    assert_eq!(head.matches("vary:").count(), 1);
+    assert!(head.contains("etag: W/\"abc\"\r\n"));
+    assert_eq!(decode("gzip", &body).len(), 5000);
+
+    let (head, _) = exchange(
+        CompressionConfig::default(),
+        "HTTP/1.1",
+        Some("gzip"),
+        text(5000)
+            .with_header(header::VARY, HeaderValue::from_static("Origin"))
+            .with_header(header::ETAG, HeaderValue::from_static("W/\"abc\""))
+            .boxed(),
+    );
+    assert!(head.contains("vary: Origin\r\nvary: Accept-Encoding\r\n"));
+    assert!(head.contains("etag: W/\"abc\"\r\n"));
+}
+
+#[test]
+fn http_10() {
+    // without chunks, the compressed body is measured before being sent
+    let (head, body) = exchange(
+        CompressionConfig::default(),
+        "HTTP/1.0",
+        Some("gzip"),
+        text(5000),
+    );
+    assert!(head.contains("content-encoding: gzip\r\n"));
+    assert!(head.contains(&format!("content-length: {}\r\n", body.len())));
+    assert_eq!(decode("gzip", &body).len(), 5000);
+}
+
+fn decompressing_server(config: DecompressionConfig) -> (tiny_http::Server, TcpStream) {
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        request_decompression: Some(config),
+        ..Default::default()
+    })
+    .unwrap();
+    let client = TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+    (server, client)
+}
+
+fn encode(encoding: &str, data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    match encoding {
+        "gzip" => flate2::read::GzEncoder::new(data, flate2::Compression::default())
+            .read_to_end(&mut encoded)
+            .unwrap(),
+        "deflate" => flate2::read::ZlibEncoder::new(data, flate2::Compression::default())
+            .read_to_end(&mut encoded)
+            .unwrap(),
+        "br" => brotli::CompressorReader::new(data, 4096, 5, 22)
+            .read_to_end(&mut encoded)
+            .unwrap(),
+        "zstd" => zstd::stream::read::Encoder::new(data, 3)
+            .unwrap()
+            .read_to_end(&mut encoded)
+            .unwrap(),
+        _ => unreachable!(),
+    };
+    encoded
+}
+
+fn send_body(client: &mut TcpStream, content_encoding: &str, body: &[u8]) {
+    write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+        content_encoding,
+        body.len()
+    )
+    .unwrap();
+    client.write_all(body).unwrap();
+}
+
+#[test]
+fn decompressed_bodies() {
+    let data = text_body(5000);
+    let (server, mut client) = decompressing_server(DecompressionConfig::default());
+
+    for encoding in ["gzip", "x-gzip", "deflate", "br", "zstd"] {
+        send_body(
+            &mut client,
+            encoding,
+            &encode(encoding.trim_start_matches("x-"), data.as_bytes()),
+        );
+
+        let mut request = server.recv().unwrap();
+        assert!(request.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(request.headers().get(header::CONTENT_LENGTH).is_none());
+        assert_eq!(request.body_length(), None);
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body).unwrap();
+        assert_eq!(body, data);
+        request.respond(Response::empty(StatusCode::OK)).unwrap();
+    }
+
+    // several encodings, applied in order
+    send_body(
+        &mut client,
+        "gzip, identity, br",
+        &encode("br", &encode("gzip", data.as_bytes())),
+    );
+    let mut body = String::new();
+    server
+        .recv()
+        .unwrap()
+        .as_reader()
+        .read_to_string(&mut body)
+        .unwrap();
+    assert_eq!(body, data);
+}
+
+#[test]
+fn chunked_compressed_body() {
+    let (server, mut client) = decompressing_server(DecompressionConfig::default());
+    let encoded = encode("gzip", b"hello world");
+    write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: gzip\r\n\
+         Transfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+        encoded.len()
+    )
+    .unwrap();
+    client.write_all(&encoded).unwrap();
+    write!(client, "\r\n0\r\n\r\n").unwrap();
+
+    let mut body = String::new();
+    server
+        .recv()
+        .unwrap()
+        .as_reader()
+        .read_to_string(&mut body)
+        .unwrap();
+    assert_eq!(body, "hello world");
+}
+
+#[test]
+fn identity_body() {
+    let (server, mut client) = decompressing_server(DecompressionConfig::default());
+    send_body(&mut client, "identity", b"hello");
+
+    let mut request = server.recv().unwrap();
+    assert_eq!(request.body_length(), Some(5));
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+}
+
+#[test]
+fn unsupported_encoding() {
+    for (config, encoding) in [
+        (DecompressionConfig::default(), "compress"),
+        (
+            DecompressionConfig::default().with_encodings([ContentEncoding::Gzip]),
+            "gzip, br",
+        ),
+    ] {
+        let (server, mut client) = decompressing_server(config);
+        send_body(&mut client, encoding, b"data");
+
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        assert!(content.starts_with("HTTP/1.1 415 Unsupported Media Type\r\n"));
+        assert!(server.try_recv().unwrap().is_none());
+    }
+}
+
+#[test]
+fn decompressed_size_limit() {
+    let (server, mut client) =
+        decompressing_server(DecompressionConfig::default().with_max_size(10_000));
+
+    let bomb = encode("gzip", &[0; 1_000_000]);
+    assert!(bomb.len() < 10_000);
+    send_body(&mut client, "gzip", &bomb);
+
+    let mut request = server.recv().unwrap();
+    let mut body = Vec::new();
+    let err = request.as_reader().read_to_end(&mut body).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(body.len(), 10_000);
+    request
+        .respond(Response::empty(StatusCode::PAYLOAD_TOO_LARGE))
+        .unwrap();
+
+    // exactly at the limit
+    send_body(&mut client, "gzip", &encode("gzip", &[0; 10_000]));
+    let mut body = Vec::new();
+    server
+        .recv()
+        .unwrap()
+        .as_reader()
+        .read_to_end(&mut body)
+        .unwrap();
+    assert_eq!(body.len(), 10_000);
+}
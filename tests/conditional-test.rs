@@ -0,0 +1,317 @@
+extern crate tiny_http;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+
+use http::{header, HeaderValue, Method, StatusCode};
+use tiny_http::{Response, ResponseBox, TestRequest};
+
+#[allow(dead_code)]
+mod support;
+
+const OLD_DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+const FUTURE_DATE: &str = "Fri, 01 Jan 2100 00:00:00 GMT";
+
+/// Answers a request with the given headers, and returns the head and the body of the
+/// response.
+fn exchange(method: &str, headers: &str, response: ResponseBox) -> (String, Vec<u8>) {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "{} / HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        method, headers
+    )
+    .unwrap();
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    support::split_response(&content)
+}
+
+fn status(head: &str) -> u16 {
+    head[9..12].parse().unwrap()
+}
+
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+}
+
+fn hello() -> ResponseBox {
+    Response::from_string("hello world").with_etag().boxed()
+}
+
+#[test]
+fn etag_of_data() {
+    let (head, _) = exchange("GET", "", hello());
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+    // the same data has the same tag
+    let response = Response::from_data("hello world").with_etag().boxed();
+    let (head, _) = exchange("GET", "", response);
+    assert_eq!(header_value(&head, "etag"), Some(&etag[..]));
+    let (head, _) = exchange(
+        "GET",
+        "",
+        Response::from_string("hello").with_etag().boxed(),
+    );
+    assert_ne!(header_value(&head, "etag"), Some(&etag[..]));
+
+    // only when asked
+    let (head, _) = exchange("GET", "", Response::from_string("hello world").boxed());
+    assert_eq!(header_value(&head, "etag"), None);
+    let response = Response::from_string("hello world").boxed();
+    let (head, _) = exchange("GET", &format!("If-None-Match: {}\r\n", etag), response);
+    assert_eq!(status(&head), 200);
+
+    // replaced, not added
+    let response = Response::from_string("hello world")
+        .with_etag()
+        .with_header(header::ETAG, HeaderValue::from_static("\"mine\""))
+        .boxed();
+    let (head, _) = exchange("GET", "", response);
+    assert_eq!(head.matches("etag").count(), 1);
+    assert_eq!(header_value(&head, "etag"), Some("\"mine\""));
+}
+
+#[test]
+fn if_none_match() {
+    let (head, _) = exchange("GET", "", hello());
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+
+    for (method, value, expected) in [
+        ("GET", etag.clone(), 304),
+        ("HEAD", etag.clone(), 304),
+        ("GET", format!("W/{}", etag), 304),
+        ("GET", format!("\"other\", {}", etag), 304),
+        ("GET", "*".to_owned(), 304),
+        ("GET", "\"other\"".to_owned(), 200),
+    ] {
+        let (head, body) = exchange(method, &format!("If-None-Match: {}\r\n", value), hello());
+        assert_eq!(status(&head), expected, "{} {}", method, value);
+        if expected == 304 {
+            assert_eq!(header_value(&head, "etag"), Some(&etag[..]));
+            assert!(body.is_empty());
+        }
+    }
+}
+
+#[test]
+fn if_match() {
+    let (head, _) = exchange("GET", "", hello());
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+
+    for (value, expected) in [
+        (etag.clone(), 200),
+        ("*".to_owned(), 200),
+        (format!("\"a,b\", {}", etag), 200),
+        // weak tags never match
+        (format!("W/{}", etag), 412),
+        ("\"other\"".to_owned(), 412),
+    ] {
+        let (head, body) = exchange("GET", &format!("If-Match: {}\r\n", value), hello());
+        assert_eq!(status(&head), expected, "{}", value);
+        if expected == 412 {
+            assert!(body.is_empty());
+        } else {
+            assert_eq!(body, b"hello world");
+        }
+    }
+}
+
+#[test]
+fn other_methods_are_not_evaluated() {
+    let (head, _) = exchange("GET", "", hello());
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+
+    // the method was already performed when the response is built
+    for (method, headers) in [
+        ("PUT", "If-None-Match: *\r\n".to_owned()),
+        ("PUT", "If-Match: \"other\"\r\n".to_owned()),
+        ("POST", "If-Match: \"other\"\r\n".to_owned()),
+        ("POST", format!("If-None-Match: {}\r\n", etag)),
+    ] {
+        let (head, _) = exchange(method, &headers, hello());
+        assert_eq!(status(&head), 200, "{} {}", method, headers);
+        let (head, _) = exchange(
+            method,
+            &headers,
+            Response::empty(StatusCode::CREATED).boxed(),
+        );
+        assert_eq!(status(&head), 201, "{} {}", method, headers);
+    }
+
+    // and not for the responses whose validators were set by hand
+    let response = Response::empty(StatusCode::OK)
+        .with_header(header::ETAG, HeaderValue::from_static("\"mine\""))
+        .boxed();
+    let (head, _) = exchange("GET", "If-None-Match: \"mine\"\r\n", response);
+    assert_eq!(status(&head), 200);
+}
+
+#[test]
+fn check_preconditions() {
+    let request = |method: Method, name: header::HeaderName, value: &'static str| {
+        let request: tiny_http::Request = TestRequest::new()
+            .with_method(method)
+            .with_header(name, HeaderValue::from_static(value))
+            .into();
+        request
+    };
+    let modified = Some(SystemTime::now());
+
+    for (method, name, value, etag, expected) in [
+        // creating a resource that must not exist
+        (Method::PUT, header::IF_NONE_MATCH, "*", None, None),
+        (
+            Method::PUT,
+            header::IF_NONE_MATCH,
+            "*",
+            Some("\"v1\""),
+            Some(StatusCode::PRECONDITION_FAILED),
+        ),
+        // updating the version that was read
+        (
+            Method::PUT,
+            header::IF_MATCH,
+            "\"v1\"",
+            Some("\"v1\""),
+            None,
+        ),
+        (
+            Method::PUT,
+            header::IF_MATCH,
+            "\"v1\"",
+            Some("\"v2\""),
+            Some(StatusCode::PRECONDITION_FAILED),
+        ),
+        (
+            Method::POST,
+            header::IF_MATCH,
+            "\"v1\"",
+            None,
+            Some(StatusCode::PRECONDITION_FAILED),
+        ),
+        (
+            Method::POST,
+            header::IF_MATCH,
+            "*",
+            None,
+            Some(StatusCode::PRECONDITION_FAILED),
+        ),
+        (Method::POST, header::IF_MATCH, "*", Some("\"v1\""), None),
+        (
+            Method::GET,
+            header::IF_NONE_MATCH,
+            "\"v1\"",
+            Some("\"v1\""),
+            Some(StatusCode::NOT_MODIFIED),
+        ),
+    ] {
+        let request = request(method.clone(), name.clone(), value);
+        assert_eq!(
+            request.check_preconditions(etag, None),
+            expected,
+            "{} {}: {} against {:?}",
+            method,
+            name,
+            value,
+            etag
+        );
+    }
+
+    let request = request(Method::DELETE, header::IF_UNMODIFIED_SINCE, OLD_DATE);
+    assert_eq!(
+        request.check_preconditions(None, modified),
+        Some(StatusCode::PRECONDITION_FAILED)
+    );
+    assert_eq!(request.check_preconditions(None, None), None);
+}
+
+#[test]
+fn unsuccessful_responses() {
+    let response = || hello().with_status_code(StatusCode::NOT_FOUND);
+    let (head, _) = exchange("GET", "If-None-Match: *\r\n", response());
+    assert_eq!(status(&head), 404);
+    let (head, _) = exchange("GET", "If-Match: \"other\"\r\n", response());
+    assert_eq!(status(&head), 404);
+}
+
+#[test]
+fn large_not_modified() {
+    let data = vec![b'a'; 100_000];
+    let response = Response::from_data(data.clone()).with_etag().boxed();
+    let (head, _) = exchange("GET", "", response);
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+
+    let (head, body) = exchange(
+        "GET",
+        &format!("If-None-Match: {}\r\n", etag),
+        Response::from_data(data).with_etag().boxed(),
+    );
+    assert_eq!(status(&head), 304);
+    assert_eq!(header_value(&head, "content-length"), Some("100000"));
+    assert!(!head.contains("transfer-encoding"));
+    assert!(body.is_empty());
+}
+
+#[test]
+fn file_validators() {
+    let file = support::TempFile::new("conditional", b"content");
+    let response = || Response::from_file(File::open(&file.0).unwrap()).boxed();
+
+    let (head, _) = exchange("GET", "", response());
+    let etag = header_value(&head, "etag").unwrap().to_owned();
+    let last_modified = header_value(&head, "last-modified").unwrap().to_owned();
+
+    for (headers, expected) in [
+        (format!("If-Modified-Since: {}\r\n", last_modified), 304),
+        (format!("If-Modified-Since: {}\r\n", FUTURE_DATE), 304),
+        (format!("If-Modified-Since: {}\r\n", OLD_DATE), 200),
+        ("If-Modified-Since: garbage\r\n".to_owned(), 200),
+        // `If-None-Match` comes first
+        (
+            format!(
+                "If-None-Match: \"other\"\r\nIf-Modified-Since: {}\r\n",
+                last_modified
+            ),
+            200,
+        ),
+        (
+            format!(
+                "If-None-Match: {}\r\nIf-Modified-Since: {}\r\n",
+                etag, OLD_DATE
+            ),
+            304,
+        ),
+        (format!("If-Unmodified-Since: {}\r\n", last_modified), 200),
+        (format!("If-Unmodified-Since: {}\r\n", OLD_DATE), 412),
+        // `If-Match` comes first
+        (
+            format!(
+                "If-Match: {}\r\nIf-Unmodified-Since: {}\r\n",
+                etag, OLD_DATE
+            ),
+            200,
+        ),
+    ] {
+        let (head, body) = exchange("GET", &headers, response());
+        assert_eq!(status(&head), expected, "{}", headers);
+        if expected == 200 {
+            assert_eq!(body, b"content");
+        } else {
+            assert!(body.is_empty());
+        }
+    }
+
+    // `If-Modified-Since` only applies to `GET` and `HEAD`
+    let (head, _) = exchange(
+        "POST",
+        &format!("If-Modified-Since: {}\r\n", last_modified),
+        response(),
+    );
+    assert_eq!(status(&head), 200);
+}
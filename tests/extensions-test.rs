@@ -0,0 +1,100 @@
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use tiny_http::{ArrivalTime, ConnectionId, TlsInfo};
+
+#[allow(dead_code)]
+mod support;
+
+#[derive(Clone, Debug, PartialEq)]
+struct User(&'static str);
+
+#[test]
+fn builtin_extensions() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let before = Instant::now();
+
+    write!(
+        client,
+        "GET /1 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+         GET /2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let first = server.recv().unwrap();
+    let second = server.recv().unwrap();
+
+    let arrival = first.extensions().get::<ArrivalTime>().unwrap().0;
+    assert!(arrival >= before && arrival <= Instant::now());
+    assert!(second.extensions().get::<ArrivalTime>().unwrap().0 >= arrival);
+
+    // both requests come from the same connection
+    let id = first.extensions().get::<ConnectionId>().unwrap();
+    assert_eq!(second.extensions().get::<ConnectionId>(), Some(id));
+    assert!(first.extensions().get::<TlsInfo>().is_none());
+
+    first
+        .respond(tiny_http::Response::from_string("1"))
+        .unwrap();
+    second
+        .respond(tiny_http::Response::from_string("2"))
+        .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+}
+
+#[test]
+fn connection_ids_differ() {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let request = server.recv().unwrap();
+        ids.push(*request.extensions().get::<ConnectionId>().unwrap());
+    }
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[test]
+fn extensions_mut() {
+    let mut request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    request.extensions_mut().insert(User("alice"));
+    assert_eq!(request.extensions().get(), Some(&User("alice")));
+
+    // moved along with the request
+    let (request, _responder) = request.into_http();
+    assert_eq!(request.extensions().get(), Some(&User("alice")));
+}
+
+#[test]
+fn test_request_extensions() {
+    let arrival = Instant::now() - Duration::from_secs(1);
+    let request: tiny_http::Request = tiny_http::TestRequest::new()
+        .with_https()
+        .with_extension(User("bob"))
+        .with_extension(ConnectionId(42))
+        .with_extension(ArrivalTime(arrival))
+        .into();
+
+    assert_eq!(request.extensions().get(), Some(&User("bob")));
+    assert_eq!(request.extensions().get(), Some(&ConnectionId(42)));
+    assert_eq!(request.extensions().get(), Some(&ArrivalTime(arrival)));
+    assert_eq!(request.extensions().get(), Some(&TlsInfo::default()));
+}
+
+#[test]
+fn test_request_builtin_extensions() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    assert_eq!(request.extensions().get(), Some(&ConnectionId(0)));
+    assert!(request.extensions().get::<ArrivalTime>().is_some());
+    assert!(request.extensions().get::<TlsInfo>().is_none());
+}
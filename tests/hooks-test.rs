@@ -0,0 +1,195 @@
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tiny_http::{ConnectionInfo, RequestError, ResponseInfo, ServerHooks};
+
+/// Records the events as strings.
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    /// Waits for the connection to be closed, and returns the events.
+    fn wait_closed(&self) -> Vec<String> {
+        for _ in 0..100 {
+            let events = self.events.lock().unwrap().clone();
+            if events.last().is_some_and(|e| e.starts_with("closed")) {
+                return events;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("connection not closed: {:?}", self.events.lock().unwrap());
+    }
+}
+
+impl ServerHooks for Recorder {
+    fn connection_opened(&self, connection: &ConnectionInfo) {
+        self.push(format!("opened {}", connection.listener_index));
+    }
+
+    fn connection_closed(&self, _connection: &ConnectionInfo) {
+        self.push("closed".to_owned());
+    }
+
+    fn request_error(&self, _connection: &ConnectionInfo, error: &RequestError) {
+        self.push(format!("error {:?}", error));
+    }
+
+    fn error_response(&self, _connection: &ConnectionInfo, status: http::StatusCode) {
+        self.push(format!("error response {}", status.as_u16()));
+    }
+
+    fn request_unanswered(&self, request: &tiny_http::Request) {
+        self.push(format!("unanswered {}", request.url()));
+    }
+
+    fn request_dispatched(&self, request: &tiny_http::Request) {
+        self.push(format!("dispatched {}", request.url()));
+    }
+
+    fn response_completed(&self, response: &ResponseInfo<'_>) {
+        self.push(format!(
+            "completed {} {}",
+            response.url,
+            response.status.as_u16()
+        ));
+    }
+}
+
+fn server_with_hooks() -> (tiny_http::Server, Arc<Recorder>, TcpStream) {
+    let recorder = Arc::new(Recorder::default());
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        hooks: Some(recorder.clone()),
+        ..Default::default()
+    })
+    .unwrap();
+    let client = TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+    (server, recorder, client)
+}
+
+#[test]
+fn request_events() {
+    let (server, recorder, mut client) = server_with_hooks();
+
+    write!(
+        client,
+        "GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+
+    assert_eq!(
+        recorder.wait_closed(),
+        [
+            "opened 0",
+            "dispatched /hello",
+            "completed /hello 200",
+            "closed"
+        ]
+    );
+}
+
+#[test]
+fn dropped_request_events() {
+    let (server, recorder, mut client) = server_with_hooks();
+
+    write!(
+        client,
+        "GET /dropped HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    drop(server.recv().unwrap());
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 500"));
+
+    assert_eq!(
+        recorder.wait_closed(),
+        [
+            "opened 0",
+            "dispatched /dropped",
+            "unanswered /dropped",
+            "error response 500",
+            "completed /dropped 500",
+            "closed"
+        ]
+    );
+}
+
+#[test]
+fn queued_request_events() {
+    let (server, recorder, mut client) = server_with_hooks();
+
+    write!(
+        client,
+        "GET /queued HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    // waits for the request to be queued, without taking it
+    while !recorder
+        .events
+        .lock()
+        .unwrap()
+        .contains(&"dispatched /queued".to_owned())
+    {
+        thread::sleep(Duration::from_millis(10));
+    }
+    drop(server);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+
+    assert_eq!(
+        recorder.wait_closed(),
+        [
+            "opened 0",
+            "dispatched /queued",
+            "unanswered /queued",
+            "error response 503",
+            "completed /queued 503",
+            "closed"
+        ]
+    );
+}
+
+#[test]
+fn parse_error_events() {
+    let (_server, recorder, mut client) = server_with_hooks();
+
+    write!(client, "GET /hello HTTP/1.1\r\nbad header\r\n\r\n").unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 400"));
+
+    assert_eq!(
+        recorder.wait_closed(),
+        [
+            "opened 0",
+            "error MalformedHeader",
+            "error response 400",
+            "closed"
+        ]
+    );
+}
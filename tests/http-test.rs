@@ -0,0 +1,234 @@
+extern crate tiny_http;
+
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::thread;
+
+#[allow(dead_code)]
+mod support;
+
+#[derive(Clone, Debug, PartialEq)]
+struct RequestId(u32);
+
+#[test]
+fn into_http_head() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new()
+        .with_method(http::Method::POST)
+        .with_path(http::Uri::from_static("/api/widgets?color=red"))
+        .with_header("X-Test".parse().unwrap(), "yes".parse().unwrap())
+        .with_header(http::header::CONTENT_LENGTH, "2".parse().unwrap())
+        .with_body("42")
+        .into();
+
+    let (request, responder) = request.into_http();
+    assert_eq!(request.method(), http::Method::POST);
+    assert_eq!(request.uri(), "/api/widgets?color=red");
+    assert_eq!(request.version(), http::Version::HTTP_11);
+    assert_eq!(request.headers()["x-test"], "yes");
+
+    let mut body = String::new();
+    request.into_body().read_to_string(&mut body).unwrap();
+    assert_eq!(body, "42");
+
+    responder
+        .respond(tiny_http::Response::from_string("done"))
+        .unwrap();
+}
+
+#[test]
+fn response_from_http() {
+    let mut response = http::Response::builder()
+        .status(http::StatusCode::CREATED)
+        .header(http::header::CONTENT_TYPE, "text/plain")
+        .header(http::header::CONTENT_LENGTH, "5")
+        .body(Cursor::new(b"hello".to_vec()))
+        .unwrap();
+    response.extensions_mut().insert(RequestId(7));
+
+    let response = tiny_http::Response::from(response);
+    assert_eq!(response.status_code(), http::StatusCode::CREATED);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    assert_eq!(response.data_length(), Some(5));
+    assert_eq!(response.extensions().get(), Some(&RequestId(7)));
+
+    let boxed = response.boxed();
+    assert_eq!(boxed.extensions().get(), Some(&RequestId(7)));
+}
+
+#[test]
+fn into_http_sends_continue_on_read() {
+    let (server, client) = support::new_one_server_one_client();
+
+    let server_thread = thread::spawn(move || {
+        let (request, responder) = server.recv().unwrap().into_http();
+        let mut body = String::new();
+        request.into_body().read_to_string(&mut body).unwrap();
+
+        let response = http::Response::builder()
+            .status(http::StatusCode::CREATED)
+            .header(http::header::CONTENT_LENGTH, body.len())
+            .body(Cursor::new(body.into_bytes()))
+            .unwrap();
+        responder.respond(response.into()).unwrap();
+    });
+
+    let mut writer = client.try_clone().unwrap();
+    write!(
+        writer,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         Expect: 100-continue\r\nContent-Length: 5\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "HTTP/1.1 100 Continue\r\n");
+
+    writer.write_all(b"hello").unwrap();
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("\r\n\r\nhello"));
+    assert!(content.ends_with("hello"));
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn into_http_early_response_skips_continue() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         Expect: 100-continue\r\nContent-Length: 5\r\n\r\n"
+    )
+    .unwrap();
+
+    let (request, responder) = server.recv().unwrap().into_http();
+    responder
+        .respond(tiny_http::Response::empty(
+            http::StatusCode::PAYLOAD_TOO_LARGE,
+        ))
+        .unwrap();
+
+    // reading the body now must not send `100 Continue` after the response
+    let mut body = request.into_body();
+    client.shutdown(std::net::Shutdown::Write).unwrap();
+    let _ = body.read(&mut [0; 5]);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 413"));
+    assert!(!content.contains("100 Continue"));
+}
+
+#[test]
+fn into_parts_head() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new()
+        .with_method(http::Method::PUT)
+        .with_path(http::Uri::from_static("/file"))
+        .with_header(http::header::CONTENT_LENGTH, "5".parse().unwrap())
+        .with_body("hello")
+        .with_extension(RequestId(3))
+        .into();
+
+    let (head, mut body, responder) = request.into_parts();
+    assert_eq!(head.method, http::Method::PUT);
+    assert_eq!(head.url, "/file");
+    assert_eq!(head.body_length, Some(5));
+    assert_eq!(head.remote_addr, Some("127.0.0.1:23456".parse().unwrap()));
+    assert!(!head.secure);
+    assert_eq!(head.extensions.get(), Some(&RequestId(3)));
+
+    let mut content = String::new();
+    body.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "hello");
+    drop(responder);
+}
+
+#[test]
+fn into_parts_early_response() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    let body = "x".repeat(5000);
+    write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .unwrap();
+
+    let (_head, mut request_body, responder) = server.recv().unwrap().into_parts();
+
+    // answering before the body is read, then reading it from another thread
+    responder
+        .respond(tiny_http::Response::from_string("accepted"))
+        .unwrap();
+    let reader = thread::spawn(move || {
+        let mut content = String::new();
+        request_body.read_to_string(&mut content).unwrap();
+        content
+    });
+    assert_eq!(reader.join().unwrap(), body);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("accepted"));
+}
+
+#[test]
+fn dropped_responder_sends_500() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let (_head, _body, responder) = server.recv().unwrap().into_parts();
+    drop(responder);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 500"));
+}
+
+#[test]
+fn responder_upgrade() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: upgrade\r\nUpgrade: echo\r\n\r\n"
+    )
+    .unwrap();
+
+    let (_head, body, responder) = server.recv().unwrap().into_parts();
+    let mut stream = responder.upgrade(
+        "echo",
+        tiny_http::Response::empty(http::StatusCode::SWITCHING_PROTOCOLS),
+        body,
+    );
+
+    let mut reader = BufReader::new(client.try_clone().unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "HTTP/1.1 101 Switching Protocols\r\n");
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+
+    client.write_all(b"ping").unwrap();
+    let mut data = [0; 4];
+    stream.read_exact(&mut data).unwrap();
+    stream.write_all(&data).unwrap();
+    stream.flush().unwrap();
+
+    let mut echoed = [0; 4];
+    reader.read_exact(&mut echoed).unwrap();
+    assert_eq!(&echoed, b"ping");
+}
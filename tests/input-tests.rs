@@ -96,6 +96,65 @@ fn invalid_header_name() {
     assert!(&content[9..].starts_with("400 Bad Request")); // 400 status code
 }
 
+#[test]
+fn custom_error_responses() {
+    use std::sync::Arc;
+    use tiny_http::{ErrorContext, ResponseBox};
+
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        error_responses: Some(Arc::new(|context: &ErrorContext<'_>| -> ResponseBox {
+            let body = format!(
+                "{{\"error\":\"{}\",\"method\":\"{}\",\"headers\":{}}}",
+                context.error,
+                context.method.unwrap(),
+                context.headers.len()
+            );
+            tiny_http::Response::from_string(body)
+                .with_status_code(context.error.status_code())
+                .with_header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                )
+                .boxed()
+        })),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mut client = std::net::TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+    (write!(
+        client,
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length : 5\r\n\r\nhello"
+    ))
+    .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 400 Bad Request"));
+    assert!(content.contains("content-type: application/json"));
+    assert!(content.ends_with(r#"{"error":"malformed header","method":"POST","headers":1}"#));
+}
+
+#[test]
+fn unsupported_http_version() {
+    let mut client = support::new_client_to_hello_world_server();
+
+    (write!(
+        client,
+        "GET / HTTP/2.0\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 505"));
+    assert!(content.ends_with("This server only supports HTTP versions 1.0 and 1.1"));
+}
+
 #[test]
 fn custom_content_type_response_header() {
     let (server, mut stream) = support::new_one_server_one_client();
@@ -121,3 +180,37 @@ fn custom_content_type_response_header() {
     assert!(content.ends_with("{\"custom\": \"Content-Type\"}"));
     assert_ne!(content.find("content-type: application/json"), None);
 }
+
+#[test]
+fn local_addr() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let port = server.server_addr().to_ip().unwrap().port();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+
+    let request = server.recv().unwrap();
+    let local_addr = request.local_addr().unwrap().clone().to_ip().unwrap();
+    assert_eq!(local_addr, client.peer_addr().unwrap());
+    assert_eq!(local_addr.port(), port);
+}
+
+#[test]
+fn test_request_local_addr() {
+    let addr: std::net::SocketAddr = "10.0.0.1:8080".parse().unwrap();
+
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    assert!(request.local_addr().is_none());
+
+    let request: tiny_http::Request = tiny_http::TestRequest::new().with_local_addr(addr).into();
+    assert_eq!(request.local_addr().unwrap().clone().to_ip(), Some(addr));
+}
+
+#[test]
+fn peer_credentials_only_for_unix() {
+    let (server, mut client) = support::new_one_server_one_client();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+
+    let request = server.recv().unwrap();
+    assert!(request.peer_credentials().is_none());
+}
@@ -121,6 +121,219 @@ fn server_crash_results_in_response() {
     assert!(&content[9..].starts_with('5')); // 5xx status code
 }
 
+#[test]
+fn multiple_listeners() {
+    use tiny_http::{ConfigListenAddr, ListenerConfig, ServerConfig};
+
+    let server = tiny_http::Server::new(ServerConfig {
+        listeners: vec![
+            ListenerConfig::new(ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap()),
+            ListenerConfig::new(ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap()),
+        ],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let addrs = server.server_addrs().to_vec();
+    assert_eq!(addrs.len(), 2);
+    assert_eq!(server.server_addr().to_ip(), addrs[0].clone().to_ip());
+
+    for (index, addr) in addrs.into_iter().enumerate() {
+        let mut client = TcpStream::connect(addr.to_ip().unwrap()).unwrap();
+        (write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        ))
+        .unwrap();
+
+        let request = server.recv().unwrap();
+        assert_eq!(request.listener_index(), index);
+        request
+            .respond(tiny_http::Response::from_string(index.to_string()))
+            .unwrap();
+
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        assert!(content.ends_with(&index.to_string()));
+    }
+}
+
+#[test]
+fn socket_options() {
+    use tiny_http::{ConfigListenAddr, ListenerConfig, ServerConfig, SocketOptions};
+
+    let server = tiny_http::Server::new(ServerConfig {
+        listeners: vec![ListenerConfig::new(
+            ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        socket_options: SocketOptions {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(60)),
+            recv_buffer_size: Some(64 * 1024),
+            send_buffer_size: Some(64 * 1024),
+            backlog: Some(16),
+        },
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mut client = TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+}
+
+#[test]
+fn no_listeners() {
+    assert!(tiny_http::Server::new(tiny_http::ServerConfig::default()).is_err());
+}
+
+#[test]
+fn stop_accepting_finishes_current_requests() {
+    let (server, mut client) = support::new_one_server_one_client();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    (write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    let request = server.recv().unwrap();
+    assert_eq!(server.num_connections(), 1);
+
+    server.stop_accepting();
+    request
+        .respond(tiny_http::Response::from_string("last response"))
+        .unwrap();
+
+    // the keep-alive connection is closed after the response
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("last response"));
+
+    for _ in 0..100 {
+        if server.num_connections() == 0 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the connection is still counted");
+}
+
+#[test]
+fn stop_accepting_closes_idle_connections() {
+    let (server, mut client) = support::new_one_server_one_client();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    while server.num_connections() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    server.stop_accepting();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    assert!(content.is_empty());
+}
+
+fn server_with_unanswered_policy(
+    policy: tiny_http::UnansweredRequestPolicy,
+) -> (tiny_http::Server, TcpStream) {
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+        )],
+        unanswered_requests: policy,
+        ..Default::default()
+    })
+    .unwrap();
+    let client = TcpStream::connect(server.server_addr().to_ip().unwrap()).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    (server, client)
+}
+
+#[test]
+fn unanswered_request_policy() {
+    use std::sync::Arc;
+
+    let (server, mut client) = server_with_unanswered_policy(tiny_http::UnansweredRequestPolicy {
+        response: Some(Arc::new(|request: &tiny_http::Request| {
+            tiny_http::Response::from_string(format!("nobody answered {}", request.url()))
+                .with_status_code(http::StatusCode::SERVICE_UNAVAILABLE)
+                .boxed()
+        })),
+        close_connection: true,
+        ..Default::default()
+    });
+
+    (write!(client, "GET /forgotten HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+    drop(server.recv().unwrap());
+
+    // the keep-alive connection is closed after the response
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+    assert!(content.ends_with("nobody answered /forgotten"));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn unanswered_request_panics_in_debug() {
+    let (server, mut client) = server_with_unanswered_policy(tiny_http::UnansweredRequestPolicy {
+        panic_in_debug: true,
+        ..Default::default()
+    });
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+    let request = server.recv().unwrap();
+    assert!(thread::spawn(move || drop(request)).join().is_err());
+
+    // the response is sent before panicking
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 500"));
+}
+
+#[test]
+fn queued_requests_skip_unanswered_policy() {
+    let (server, mut client) = server_with_unanswered_policy(tiny_http::UnansweredRequestPolicy {
+        panic_in_debug: true,
+        ..Default::default()
+    });
+
+    (write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    ))
+    .unwrap();
+    // waits for the request to be queued, without taking it
+    while server.num_connections() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    thread::sleep(Duration::from_millis(100));
+    drop(server);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 503"));
+}
+
 #[test]
 fn responses_reordered() {
     let (server, mut client) = support::new_one_server_one_client();
@@ -0,0 +1,204 @@
+extern crate tiny_http;
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+use http::{header, HeaderValue};
+
+#[allow(dead_code)]
+mod support;
+
+const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+fn data() -> Vec<u8> {
+    (0..1000).map(|i| (i % 251) as u8).collect()
+}
+
+/// Answers a request with the given headers with the file, and returns the head and the
+/// body of the response.
+fn exchange(name: &str, method: &str, headers: &str) -> (String, Vec<u8>) {
+    let file = support::TempFile::new(&format!("range-{}", name), &data());
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "{} / HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        method, headers
+    )
+    .unwrap();
+
+    let response = tiny_http::Response::from_file(File::open(&file.0).unwrap())
+        .with_header(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+        .with_header(header::ETAG, HeaderValue::from_static("\"v1\""))
+        .with_header(
+            header::LAST_MODIFIED,
+            HeaderValue::from_static(LAST_MODIFIED),
+        );
+    server.recv().unwrap().respond(response).unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    support::split_response(&content)
+}
+
+#[test]
+fn whole_file() {
+    let (head, body) = exchange("whole", "GET", "");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("accept-ranges: bytes\r\n"));
+    assert!(head.contains("content-length: 1000\r\n"));
+    assert!(!head.contains("content-range"));
+    assert_eq!(body, data());
+}
+
+#[test]
+fn single_range() {
+    for (range, first, last) in [
+        ("bytes=0-99", 0, 99),
+        ("bytes=900-", 900, 999),
+        ("bytes=-10", 990, 999),
+        ("bytes=-5000", 0, 999),
+        ("bytes=995-5000", 995, 999),
+        ("BYTES = 10-10", 10, 10),
+        // a single satisfiable range
+        ("bytes=2000-, 5-6", 5, 6),
+    ] {
+        let (head, body) = exchange("single", "GET", &format!("Range: {}\r\n", range));
+        assert!(
+            head.starts_with("HTTP/1.1 206 Partial Content\r\n"),
+            "{}",
+            range
+        );
+        assert!(head.contains(&format!("content-range: bytes {}-{}/1000\r\n", first, last)));
+        assert!(head.contains(&format!("content-length: {}\r\n", last - first + 1)));
+        assert!(head.contains("content-type: text/plain\r\n"));
+        assert_eq!(body, &data()[first..=last]);
+    }
+}
+
+#[test]
+fn multiple_ranges() {
+    let (head, body) = exchange("multiple", "GET", "Range: bytes=0-9, 500-504, -3\r\n");
+    assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(!head.contains("content-range"));
+
+    let boundary = head
+        .lines()
+        .find_map(|line| line.strip_prefix("content-type: multipart/byteranges; boundary="))
+        .unwrap();
+    let length: usize = head
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length: "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(body.len(), length);
+
+    let mut expected = Vec::new();
+    for (first, last) in [(0, 9), (500, 504), (997, 999)] {
+        write!(
+            expected,
+            "\r\n--{}\r\nContent-Type: text/plain\r\nContent-Range: bytes {}-{}/1000\r\n\r\n",
+            boundary, first, last
+        )
+        .unwrap();
+        expected.extend_from_slice(&data()[first..=last]);
+    }
+    write!(expected, "\r\n--{}--\r\n", boundary).unwrap();
+    assert_eq!(body, expected);
+}
+
+#[test]
+fn overlapping_ranges() {
+    // merged, so that no byte is sent twice
+    let many = vec!["0-"; 64].join(",");
+    for (range, first, last) in [
+        (many.as_str(), 0, 999),
+        ("50-149, 0-99", 0, 149),
+        ("0-99, 100-199", 0, 199),
+        ("900-, -50", 900, 999),
+    ] {
+        let (head, body) = exchange("overlapping", "GET", &format!("Range: bytes={}\r\n", range));
+        assert!(
+            head.starts_with("HTTP/1.1 206 Partial Content\r\n"),
+            "{}",
+            range
+        );
+        assert!(head.contains(&format!("content-range: bytes {}-{}/1000\r\n", first, last)));
+        assert_eq!(body, &data()[first..=last]);
+    }
+
+    // sorted, with one part per merged range
+    let (head, body) = exchange(
+        "overlapping",
+        "GET",
+        "Range: bytes=500-599, 0-99, 50-149\r\n",
+    );
+    assert!(head.contains("multipart/byteranges"));
+    let body = String::from_utf8_lossy(&body);
+    assert_eq!(body.matches("Content-Range").count(), 2);
+    let first = body.find("Content-Range: bytes 0-149/1000").unwrap();
+    let second = body.find("Content-Range: bytes 500-599/1000").unwrap();
+    assert!(first < second);
+}
+
+#[test]
+fn unsatisfiable_range() {
+    for range in ["bytes=1000-", "bytes=5000-6000, 1000-1000", "bytes=-0"] {
+        let (head, body) = exchange("unsatisfiable", "GET", &format!("Range: {}\r\n", range));
+        assert!(
+            head.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"),
+            "{}",
+            range
+        );
+        assert!(head.contains("content-range: bytes */1000\r\n"));
+        assert!(body.is_empty());
+    }
+}
+
+#[test]
+fn ignored_range() {
+    // invalid, in another unit, or not for a GET request
+    for (method, range) in [
+        ("GET", "bytes=10-5"),
+        ("GET", "bytes=a-b"),
+        ("GET", "items=0-5"),
+        ("POST", "bytes=0-5"),
+    ] {
+        let (head, body) = exchange("ignored", method, &format!("Range: {}\r\n", range));
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", range);
+        assert_eq!(body, data());
+    }
+}
+
+#[test]
+fn if_range() {
+    for (if_range, partial) in [
+        ("\"v1\"", true),
+        ("\"v2\"", false),
+        ("W/\"v1\"", false),
+        (LAST_MODIFIED, true),
+        ("Thu, 22 Oct 2015 07:28:00 GMT", false),
+        ("garbage", false),
+    ] {
+        let (head, body) = exchange(
+            "if-range",
+            "GET",
+            &format!("Range: bytes=0-9\r\nIf-Range: {}\r\n", if_range),
+        );
+        if partial {
+            assert!(head.starts_with("HTTP/1.1 206"), "{}", if_range);
+            assert_eq!(body, &data()[..10]);
+        } else {
+            assert!(head.starts_with("HTTP/1.1 200"), "{}", if_range);
+            assert_eq!(body, data());
+        }
+    }
+}
+
+#[test]
+fn head_request() {
+    let (head, body) = exchange("head", "HEAD", "Range: bytes=0-9\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("accept-ranges: bytes\r\n"));
+    assert!(body.is_empty());
+}
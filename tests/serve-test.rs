@@ -0,0 +1,112 @@
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[allow(dead_code)]
+mod support;
+
+fn hello(request: tiny_http::Request) {
+    if request.url() == "/panic" {
+        panic!("handler failure");
+    }
+    request
+        .respond(tiny_http::Response::from_string("hello world"))
+        .unwrap();
+}
+
+#[test]
+fn serve_answers_requests() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let server = Arc::new(server);
+
+    let s = server.clone();
+    let workers = thread::spawn(move || s.serve(hello, 2));
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+
+    server.unblock();
+    workers.join().unwrap();
+}
+
+#[test]
+fn serve_survives_panics() {
+    let (server, mut client) = support::new_one_server_one_client();
+    let server = Arc::new(server);
+
+    let s = server.clone();
+    let workers = thread::spawn(move || s.serve(hello, 1));
+
+    write!(client, "GET /panic HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut content = [0; 12];
+    client.read_exact(&mut content).unwrap();
+    assert_eq!(&content, b"HTTP/1.1 500");
+
+    // the only worker is still there
+    let port = server.server_addr().to_ip().unwrap().port();
+    let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+
+    server.unblock();
+    workers.join().unwrap();
+}
+
+#[test]
+fn serve_stops_every_worker_on_unblock() {
+    let server = Arc::new(tiny_http::Server::http("0.0.0.0:0").unwrap());
+
+    let s = server.clone();
+    let workers = thread::spawn(move || s.serve(hello, 4));
+
+    thread::sleep(Duration::from_millis(50));
+    server.unblock();
+    workers.join().unwrap();
+}
+
+#[test]
+fn serve_returns_once_drained() {
+    let (server, mut client) = support::new_one_server_one_client();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let server = Arc::new(server);
+
+    let s = server.clone();
+    let workers = thread::spawn(move || {
+        s.serve(
+            |request| {
+                // stop while a request is being handled
+                s.stop_accepting();
+                thread::sleep(Duration::from_millis(50));
+                request
+                    .respond(tiny_http::Response::from_string("last response"))
+                    .unwrap();
+            },
+            2,
+        )
+    });
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("last response"));
+
+    workers.join().unwrap();
+    assert_eq!(server.num_connections(), 0);
+}
@@ -0,0 +1,175 @@
+extern crate tiny_http;
+
+use std::io::{BufRead, BufReader, Write};
+use std::thread;
+use std::time::Duration;
+
+use http::HeaderMap;
+use tiny_http::sse::Event;
+
+#[allow(dead_code)]
+mod support;
+
+/// Reads the next chunk of a chunked body.
+fn read_chunk<R: BufRead>(reader: &mut R) -> String {
+    let mut size = String::new();
+    reader.read_line(&mut size).unwrap();
+    let size = usize::from_str_radix(size.trim_end(), 16).unwrap();
+    let mut data = vec![0; size + 2];
+    reader.read_exact(&mut data).unwrap();
+    data.truncate(size);
+    String::from_utf8(data).unwrap()
+}
+
+#[test]
+fn events_are_sent_right_away() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /events HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut events = server
+        .recv()
+        .unwrap()
+        .respond_events(HeaderMap::new())
+        .unwrap();
+
+    let mut reader = BufReader::new(client);
+    let mut head = String::new();
+    while !head.ends_with("\r\n\r\n") {
+        reader.read_line(&mut head).unwrap();
+    }
+    assert!(head.contains("content-type: text/event-stream\r\n"));
+    assert!(head.contains("cache-control: no-cache\r\n"));
+    assert!(head.contains("transfer-encoding: chunked\r\n"));
+
+    events
+        .send(&Event::new("tick").with_event("clock"))
+        .unwrap();
+    assert_eq!(read_chunk(&mut reader), "event: clock\ndata: tick\n\n");
+
+    events.comment("still there").unwrap();
+    assert_eq!(read_chunk(&mut reader), ": still there\n\n");
+
+    drop(events);
+    assert_eq!(read_chunk(&mut reader), "");
+}
+
+#[test]
+fn multiline_data() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let mut events = server
+        .recv()
+        .unwrap()
+        .respond_events(HeaderMap::new())
+        .unwrap();
+    let event = Event::new("first\nsecond\r\nthird")
+        .with_event("up\ndate")
+        .with_id("4\r2")
+        .with_retry(Duration::from_millis(1500));
+    events.send(&event).unwrap();
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    assert_eq!(
+        read_chunk(&mut reader),
+        "event: update\nid: 42\nretry: 1500\ndata: first\ndata: second\ndata: third\n\n"
+    );
+}
+
+#[test]
+fn last_event_id() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new()
+        .with_header("Last-Event-ID".parse().unwrap(), "41".parse().unwrap())
+        .into();
+    assert_eq!(request.last_event_id(), Some("41"));
+
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    assert_eq!(request.last_event_id(), None);
+}
+
+#[test]
+fn keep_alive_comments() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let events = server
+        .recv()
+        .unwrap()
+        .respond_events(HeaderMap::new())
+        .unwrap()
+        .with_keep_alive(Duration::from_millis(50));
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    assert_eq!(read_chunk(&mut reader), ":\n\n");
+    assert_eq!(read_chunk(&mut reader), ":\n\n");
+    drop(events);
+}
+
+#[test]
+fn disconnect_is_detected() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let events = server
+        .recv()
+        .unwrap()
+        .respond_events(HeaderMap::new())
+        .unwrap()
+        .with_keep_alive(Duration::from_millis(20));
+    drop(client);
+
+    for _ in 0..100 {
+        if events.is_closed() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("the disconnection was not detected");
+}
+
+#[test]
+fn keep_alive_interval_can_be_changed() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let events = server
+        .recv()
+        .unwrap()
+        .respond_events(HeaderMap::new())
+        .unwrap()
+        .with_keep_alive(Duration::from_secs(3600))
+        .with_keep_alive(Duration::from_millis(50));
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    assert_eq!(read_chunk(&mut reader), ":\n\n");
+    drop(events);
+}
+
+#[test]
+#[should_panic(expected = "keep-alive interval")]
+fn zero_keep_alive_interval() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    let _ = request
+        .respond_events(HeaderMap::new())
+        .unwrap()
+        .with_keep_alive(Duration::ZERO);
+}
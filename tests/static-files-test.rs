@@ -0,0 +1,443 @@
+extern crate tiny_http;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use rustc_serialize::json::Json;
+use tiny_http::StaticFiles;
+
+#[allow(dead_code)]
+mod support;
+
+/// Creates a directory with a `root` directory to serve and a `secret.txt` file outside of it.
+fn site(name: &str) -> support::TempDir {
+    let dir = support::TempDir::new(&format!("static-{}", name));
+    let root = dir.0.join("root");
+    fs::create_dir_all(root.join("sub/empty")).unwrap();
+
+    for (name, content) in [
+        ("index.html", "<h1>home</h1>"),
+        ("style.css", "body {}"),
+        ("a b.txt", "spaces"),
+        ("unknown.xyz", "?"),
+        ("data.json", "{}"),
+        ("data.json.gz", "gzip bytes"),
+        ("data.json.br", "brotli bytes"),
+        ("sub/file.txt", "in sub"),
+    ] {
+        fs::write(root.join(name), content).unwrap();
+    }
+    fs::write(dir.0.join("secret.txt"), "secret").unwrap();
+
+    dir
+}
+
+fn root(dir: &support::TempDir) -> PathBuf {
+    dir.0.join("root")
+}
+
+/// Sends a request for `url` with the given headers to the files, and returns the head and
+/// the body of the response.
+fn exchange(files: &StaticFiles, method: &str, url: &str, headers: &str) -> (String, Vec<u8>) {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        method, url, headers
+    )
+    .unwrap();
+    files.serve(server.recv().unwrap()).unwrap();
+
+    let mut content = Vec::new();
+    client.read_to_end(&mut content).unwrap();
+    support::split_response(&content)
+}
+
+fn get(files: &StaticFiles, url: &str) -> (String, Vec<u8>) {
+    exchange(files, "GET", url, "")
+}
+
+#[test]
+fn files() {
+    let dir = site("files");
+    let files = StaticFiles::new(root(&dir));
+
+    for (url, body, content_type) in [
+        ("/", "<h1>home</h1>", "text/html; charset=utf-8"),
+        ("/index.html", "<h1>home</h1>", "text/html; charset=utf-8"),
+        ("/style.css", "body {}", "text/css; charset=utf-8"),
+        ("/a%20b.txt", "spaces", "text/plain; charset=utf-8"),
+        ("/sub/file.txt", "in sub", "text/plain; charset=utf-8"),
+        (
+            "/sub/./../sub//file.txt",
+            "in sub",
+            "text/plain; charset=utf-8",
+        ),
+        (
+            "/%73ub/file.txt?query",
+            "in sub",
+            "text/plain; charset=utf-8",
+        ),
+        ("/unknown.xyz", "?", "application/octet-stream"),
+    ] {
+        let (head, content) = get(&files, url);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", url);
+        assert!(
+            head.contains(&format!("content-type: {}\r\n", content_type)),
+            "{}",
+            url
+        );
+        assert!(head.contains("last-modified: "));
+        assert_eq!(content, body.as_bytes());
+    }
+
+    let (head, content) = exchange(&files, "HEAD", "/style.css", "");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("content-length: 7\r\n"));
+    assert!(content.is_empty());
+}
+
+#[test]
+fn not_found() {
+    let dir = site("not-found");
+    let files = StaticFiles::new(root(&dir));
+
+    for url in [
+        "/missing",
+        "/style.css/",
+        // no index file
+        "/sub/empty/",
+        // outside of the root directory
+        "/../secret.txt",
+        "/sub/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/sub%2F..%2F..%2Fsecret.txt",
+        "/..%5Csecret.txt",
+        // not percent-encoded properly
+        "/style%2",
+        "/%zz",
+        "/%ff",
+    ] {
+        let (head, _) = get(&files, url);
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", url);
+    }
+
+    let files = StaticFiles::new(root(&dir)).with_index_files(["missing.html"]);
+    let (head, _) = get(&files, "/");
+    assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn index_files() {
+    let dir = site("index-files");
+    let files = StaticFiles::new(root(&dir)).with_index_files(["index.htm", "file.txt"]);
+
+    let (head, content) = get(&files, "/sub/");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert_eq!(content, b"in sub");
+}
+
+#[test]
+fn trailing_slash_redirect() {
+    let dir = site("redirect");
+    let files = StaticFiles::new(root(&dir));
+
+    for (url, location) in [
+        ("/sub", "/sub/"),
+        ("/sub?a=1&b", "/sub/?a=1&b"),
+        ("/sub/empty", "/sub/empty/"),
+        ("//sub", "/sub/"),
+    ] {
+        let (head, content) = get(&files, url);
+        assert!(
+            head.starts_with("HTTP/1.1 301 Moved Permanently\r\n"),
+            "{}",
+            url
+        );
+        assert!(
+            head.contains(&format!("location: {}\r\n", location)),
+            "{}",
+            url
+        );
+        assert!(content.is_empty());
+    }
+}
+
+#[test]
+fn methods() {
+    let dir = site("methods");
+    let files = StaticFiles::new(root(&dir));
+
+    let (head, _) = exchange(&files, "POST", "/style.css", "Content-Length: 0\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(head.contains("allow: GET, HEAD\r\n"));
+
+    let (head, _) = exchange(&files, "DELETE", "/missing", "");
+    assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn conditional_and_ranges() {
+    let dir = site("conditional");
+    let files = StaticFiles::new(root(&dir));
+
+    let (head, _) = get(&files, "/style.css");
+    let etag = head
+        .lines()
+        .find_map(|line| line.strip_prefix("etag: "))
+        .unwrap();
+    let (head, content) = exchange(
+        &files,
+        "GET",
+        "/style.css",
+        &format!("If-None-Match: {}\r\n", etag),
+    );
+    assert!(head.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    assert!(content.is_empty());
+
+    let (head, content) = exchange(&files, "GET", "/style.css", "Range: bytes=0-3\r\n");
+    assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert_eq!(content, b"body");
+}
+
+#[test]
+fn precompressed() {
+    let dir = site("precompressed");
+
+    // disabled by default
+    let files = StaticFiles::new(root(&dir));
+    let (head, content) = exchange(&files, "GET", "/data.json", "Accept-Encoding: gzip\r\n");
+    assert!(!head.contains("content-encoding"));
+    assert!(!head.contains("vary"));
+    assert_eq!(content, b"{}");
+
+    let files = StaticFiles::new(root(&dir)).with_precompressed(true);
+    for (accept_encoding, encoding, body) in [
+        ("gzip", Some("gzip"), "gzip bytes"),
+        ("x-gzip", Some("gzip"), "gzip bytes"),
+        ("br, gzip", Some("br"), "brotli bytes"),
+        ("gzip, br;q=0.5", Some("gzip"), "gzip bytes"),
+        ("*", Some("br"), "brotli bytes"),
+        ("br;q=0, *", Some("gzip"), "gzip bytes"),
+        ("deflate", None, "{}"),
+        ("", None, "{}"),
+    ] {
+        let (head, content) = exchange(
+            &files,
+            "GET",
+            "/data.json",
+            &format!("Accept-Encoding: {}\r\n", accept_encoding),
+        );
+        match encoding {
+            Some(encoding) => assert!(
+                head.contains(&format!("content-encoding: {}\r\n", encoding)),
+                "{}",
+                accept_encoding
+            ),
+            None => assert!(!head.contains("content-encoding"), "{}", accept_encoding),
+        }
+        assert!(head.contains("content-type: application/json\r\n"));
+        assert!(head.contains("vary: Accept-Encoding\r\n"));
+        assert_eq!(content, body.as_bytes(), "{}", accept_encoding);
+    }
+
+    // without compressed copies
+    let (head, _) = exchange(&files, "GET", "/style.css", "Accept-Encoding: gzip\r\n");
+    assert!(!head.contains("content-encoding"));
+    assert!(!head.contains("vary"));
+
+    // the copies themselves can still be requested
+    let (head, content) = get(&files, "/data.json.gz");
+    assert!(head.contains("content-type: application/gzip\r\n"));
+    assert!(!head.contains("content-encoding"));
+    assert_eq!(content, b"gzip bytes");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+    let dir = site("symlinks");
+    std::os::unix::fs::symlink(dir.0.join("secret.txt"), root(&dir).join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(root(&dir).join("sub"), root(&dir).join("linked")).unwrap();
+
+    let files = StaticFiles::new(root(&dir));
+    let (_, content) = get(&files, "/link.txt");
+    assert_eq!(content, b"secret");
+    let (_, content) = get(&files, "/linked/file.txt");
+    assert_eq!(content, b"in sub");
+
+    let files = StaticFiles::new(root(&dir)).with_symlinks(false);
+    for url in ["/link.txt", "/linked/file.txt", "/linked/"] {
+        let (head, _) = get(&files, url);
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", url);
+    }
+    let (head, _) = get(&files, "/sub/file.txt");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    // and are not listed
+    let files = files.with_listing(true).with_index_files(["missing.html"]);
+    let (_, content) = get(&files, "/sub/");
+    assert!(String::from_utf8(content).unwrap().contains("file.txt"));
+    let (_, content) = get(&files, "/");
+    assert!(!String::from_utf8(content).unwrap().contains("link"));
+}
+
+/// Lists the root directory as JSON, and returns the names of its entries.
+fn listed_names(files: &StaticFiles, url: &str) -> Vec<String> {
+    let (head, content) = exchange(files, "GET", url, "Accept: application/json\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", url);
+    let json = Json::from_str(std::str::from_utf8(&content).unwrap()).unwrap();
+    json["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["name"].as_string().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn listing() {
+    let dir = site("listing");
+    fs::write(root(&dir).join("sub/<b>&.txt"), "markup").unwrap();
+    fs::write(root(&dir).join("sub/.hidden"), "hidden").unwrap();
+    let files = StaticFiles::new(root(&dir)).with_listing(true);
+
+    let (head, content) = get(&files, "/sub/");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("content-type: text/html; charset=utf-8\r\n"));
+    assert!(head.contains("vary: Accept\r\n"));
+    let html = String::from_utf8(content).unwrap();
+    assert!(html.contains("<title>Index of /sub/</title>"));
+    assert!(html.contains("<a href=\"../\">../</a>"));
+    assert!(html.contains("<a href=\"empty/\">empty/</a>"));
+    assert!(html.contains("<a href=\"file.txt\">file.txt</a></td><td>6</td>"));
+    assert!(html.contains("<a href=\"%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a>"));
+    assert!(html.contains("<td>text/plain</td>"));
+    assert!(!html.contains("hidden"));
+    // directories first
+    assert!(html.find("empty/").unwrap() < html.find("file.txt").unwrap());
+
+    // an index file comes first
+    let (_, content) = get(&files, "/");
+    assert_eq!(content, b"<h1>home</h1>");
+
+    let files = files.with_hidden_files(true);
+    let (_, content) = get(&files, "/sub/");
+    assert!(String::from_utf8(content).unwrap().contains(".hidden"));
+}
+
+#[test]
+fn listing_json() {
+    let dir = site("listing-json");
+    let files = StaticFiles::new(root(&dir)).with_listing(true);
+
+    let (head, content) = exchange(&files, "GET", "/sub/", "Accept: application/json\r\n");
+    assert!(head.contains("content-type: application/json\r\n"));
+    let json = Json::from_str(std::str::from_utf8(&content).unwrap()).unwrap();
+    assert_eq!(json["path"].as_string(), Some("/sub/"));
+
+    let entries = json["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["name"].as_string(), Some("empty"));
+    assert_eq!(entries[0]["type"].as_string(), Some("directory"));
+    assert!(entries[0]["size"].is_null());
+    assert_eq!(entries[1]["name"].as_string(), Some("file.txt"));
+    assert_eq!(entries[1]["type"].as_string(), Some("text/plain"));
+    assert_eq!(entries[1]["size"].as_u64(), Some(6));
+    assert!(entries[1]["modified"].as_u64().unwrap() > 1_500_000_000);
+
+    for (accept, json) in [
+        ("application/json", true),
+        ("application/*", true),
+        ("text/html, application/json;q=0.9", false),
+        ("text/html;q=0.5, application/json", true),
+        ("*/*", false),
+        ("text/*, */*;q=0.1", false),
+        ("", false),
+    ] {
+        let (head, _) = exchange(&files, "GET", "/sub/", &format!("Accept: {}\r\n", accept));
+        assert_eq!(
+            head.contains("content-type: application/json\r\n"),
+            json,
+            "{}",
+            accept
+        );
+    }
+}
+
+#[test]
+fn listing_sort() {
+    let dir = site("listing-sort");
+    let files = StaticFiles::new(root(&dir))
+        .with_listing(true)
+        .with_index_files(["missing.html"]);
+
+    for (query, names) in [
+        (
+            "",
+            [
+                "sub",
+                "a b.txt",
+                "data.json",
+                "data.json.br",
+                "data.json.gz",
+                "index.html",
+                "style.css",
+                "unknown.xyz",
+            ],
+        ),
+        (
+            "?sort=name&order=desc",
+            [
+                "sub",
+                "unknown.xyz",
+                "style.css",
+                "index.html",
+                "data.json.gz",
+                "data.json.br",
+                "data.json",
+                "a b.txt",
+            ],
+        ),
+        (
+            "?sort=size&order=desc",
+            [
+                "sub",
+                "index.html",
+                "data.json.br",
+                "data.json.gz",
+                "style.css",
+                "a b.txt",
+                "data.json",
+                "unknown.xyz",
+            ],
+        ),
+        (
+            "?order=asc&sort=type",
+            [
+                "sub",
+                "data.json.gz",
+                "data.json",
+                "data.json.br",
+                "unknown.xyz",
+                "style.css",
+                "index.html",
+                "a b.txt",
+            ],
+        ),
+    ] {
+        assert_eq!(
+            listed_names(&files, &format!("/{}", query)),
+            names,
+            "{}",
+            query
+        );
+    }
+
+    // the links of the columns toggle the order
+    let (_, content) = get(&files, "/?sort=size");
+    let html = String::from_utf8(content).unwrap();
+    assert!(html.contains("<a href=\"?sort=size&amp;order=desc\">Size</a>"));
+    assert!(html.contains("<a href=\"?sort=name&amp;order=asc\">Name</a>"));
+    assert!(!html.contains("../"));
+}
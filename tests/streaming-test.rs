@@ -0,0 +1,209 @@
+extern crate tiny_http;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use http::{header, HeaderMap, StatusCode};
+
+#[allow(dead_code)]
+mod support;
+
+/// Reads the status line and the headers of a response.
+fn read_head(reader: &mut BufReader<TcpStream>) -> String {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        head.push_str(&line);
+        if line == "\r\n" || line.is_empty() {
+            return head;
+        }
+    }
+}
+
+#[test]
+fn chunked_with_http11() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+    headers.insert(header::CONTENT_LENGTH, "1000".parse().unwrap());
+    let mut writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, headers)
+        .unwrap();
+
+    // the head is sent right away
+    let mut reader = BufReader::new(client);
+    let head = read_head(&mut reader);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("transfer-encoding: chunked\r\n"));
+    assert!(head.contains("content-type: text/plain\r\n"));
+    assert!(!head.contains("content-length"));
+
+    // each flush sends a chunk
+    write!(writer, "hello").unwrap();
+    writer.flush().unwrap();
+    let mut chunk = [0; 10];
+    reader.read_exact(&mut chunk).unwrap();
+    assert_eq!(&chunk, b"5\r\nhello\r\n");
+
+    write!(writer, " world").unwrap();
+    writer.flush().unwrap();
+    let mut chunk = [0; 11];
+    reader.read_exact(&mut chunk).unwrap();
+    assert_eq!(&chunk, b"6\r\n world\r\n");
+
+    writer.finish().unwrap();
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "0\r\n\r\n");
+}
+
+#[test]
+fn trailers() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::TRAILER, "x-checksum".parse().unwrap());
+    let mut writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, headers)
+        .unwrap();
+    write!(writer, "data").unwrap();
+    writer
+        .trailers_mut()
+        .insert("x-checksum", "1234".parse().unwrap());
+    writer.finish().unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("\r\n\r\n4\r\ndata\r\n0\r\nx-checksum: 1234\r\n\r\n"));
+}
+
+#[test]
+fn close_delimited_with_http10() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+    let mut writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, HeaderMap::new())
+        .unwrap();
+    write!(writer, "hello").unwrap();
+    writer.flush().unwrap();
+    write!(writer, " world").unwrap();
+    drop(writer);
+
+    // the end of the body is the end of the connection
+    let mut reader = BufReader::new(client);
+    let head = read_head(&mut reader);
+    assert!(head.starts_with("HTTP/1.0 200 OK\r\n"));
+    assert!(head.contains("connection: close\r\n"));
+    assert!(!head.contains("transfer-encoding"));
+
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "hello world");
+}
+
+#[test]
+fn drop_finishes_the_body() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "GET /1 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+         GET /2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, HeaderMap::new())
+        .unwrap();
+    write!(writer, "first").unwrap();
+    drop(writer);
+
+    // the connection can be used for the next request
+    server
+        .recv()
+        .unwrap()
+        .respond(tiny_http::Response::from_string("second"))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("\r\n\r\n5\r\nfirst\r\n0\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    assert!(content.ends_with("second"));
+}
+
+#[test]
+fn no_body_for_head_requests() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "HEAD / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, HeaderMap::new())
+        .unwrap();
+    write!(writer, "ignored").unwrap();
+    writer.finish().unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("transfer-encoding: chunked\r\n"));
+    assert!(content.ends_with("\r\n\r\n"));
+    assert!(!content.contains("ignored"));
+}
+
+#[test]
+fn head_request_with_http10_closes_the_connection() {
+    let (server, mut client) = support::new_one_server_one_client();
+    client
+        .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+        .unwrap();
+    write!(client, "HEAD / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+
+    let writer = server
+        .recv()
+        .unwrap()
+        .respond_streaming(StatusCode::OK, HeaderMap::new())
+        .unwrap();
+    writer.finish().unwrap();
+
+    // the head says `Connection: close`, so the connection must be closed
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.contains("connection: close\r\n"));
+    assert!(content.ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn no_body_for_204() {
+    let request: tiny_http::Request = tiny_http::TestRequest::new().into();
+    let mut writer = request
+        .respond_streaming(StatusCode::NO_CONTENT, HeaderMap::new())
+        .unwrap();
+    write!(writer, "ignored").unwrap();
+    writer.flush().unwrap();
+    writer.finish().unwrap();
+}
@@ -1,4 +1,6 @@
+use std::fs;
 use std::net::TcpStream;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
@@ -38,3 +40,52 @@ pub fn new_client_to_hello_world_server() -> TcpStream {
 
     client
 }
+
+/// Splits a raw response into its head, with the empty line, and its body.
+pub fn split_response(content: &[u8]) -> (String, Vec<u8>) {
+    let split = content.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8(content[..split].to_vec()).unwrap();
+    (head, content[split..].to_vec())
+}
+
+/// Path in the temporary directory that is unique to the test process and to `name`.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("tiny-http-{}-{}", std::process::id(), name))
+}
+
+/// A file removed once the test is done.
+pub struct TempFile(pub PathBuf);
+
+impl TempFile {
+    /// Creates a file with the given content.
+    pub fn new(name: &str, content: &[u8]) -> TempFile {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// A directory removed with its content once the test is done.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory.
+    pub fn new(name: &str) -> TempDir {
+        let path = temp_path(name);
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
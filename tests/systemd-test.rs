@@ -0,0 +1,164 @@
+#![cfg(target_os = "linux")]
+
+extern crate tiny_http;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+
+/// Runs in the child process started by `socket_activation`, with the sockets passed as
+/// file descriptors 3 and 4 like systemd would.
+#[test]
+#[ignore]
+fn socket_activation_child() {
+    if std::env::var_os("TINY_HTTP_SYSTEMD_CHILD").is_none() {
+        return;
+    }
+
+    // the requests are answered by the policy of the configuration
+    let config = tiny_http::ServerConfig {
+        unanswered_requests: tiny_http::UnansweredRequestPolicy {
+            response: Some(Arc::new(|request| {
+                let response = format!("listener {}", request.listener_index());
+                tiny_http::Response::from_string(response).boxed()
+            })),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut names = Vec::new();
+    let server = tiny_http::Server::from_systemd_with_config(
+        |name| {
+            names.push(name.to_owned());
+            None
+        },
+        config,
+    )
+    .unwrap();
+    assert_eq!(names, ["web", "admin"]);
+    assert_eq!(server.server_addrs().len(), 2);
+
+    // claimed only once, and the environment is left alone unless asked
+    assert!(tiny_http::Listener::from_systemd().unwrap().is_empty());
+    assert!(std::env::var_os("LISTEN_FDS").is_some());
+    assert!(tiny_http::Server::from_systemd(|_| None).is_err());
+
+    for _ in 0..2 {
+        drop(server.recv().unwrap());
+    }
+}
+
+/// Starts the test `name` in a child process, with `fds` passed as the file descriptors 3 and
+/// onwards like systemd would.
+fn spawn_activated(name: &str, fds: &[RawFd]) -> Child {
+    let fds = fds.to_vec();
+
+    // `exec` keeps the PID of the shell, which is what `LISTEN_PID` must contain
+    let mut child = Command::new("sh");
+    child
+        .arg("-c")
+        .arg("LISTEN_PID=$$ exec \"$0\" \"$@\"")
+        .arg(std::env::current_exe().unwrap())
+        .args(["--exact", name, "--ignored"])
+        .env("TINY_HTTP_SYSTEMD_CHILD", "1")
+        .env("LISTEN_FDS", fds.len().to_string())
+        .env("LISTEN_FDNAMES", "web:admin")
+        .stdout(Stdio::null());
+    unsafe {
+        child.pre_exec(move || {
+            // move the sockets out of the way first, in case one of them already is in place
+            for (offset, fd) in fds.iter().enumerate() {
+                let tmp = 100 + offset as RawFd;
+                let _ = nix::unistd::dup2_raw(BorrowedFd::borrow_raw(*fd), tmp)?.into_raw_fd();
+            }
+            for offset in 0..fds.len() as RawFd {
+                let tmp = BorrowedFd::borrow_raw(100 + offset);
+                let _ = nix::unistd::dup2_raw(tmp, 3 + offset)?.into_raw_fd();
+            }
+            Ok(())
+        });
+    }
+    child.spawn().unwrap()
+}
+
+#[test]
+fn socket_activation() {
+    let path = Path::new("/tmp/tiny-http-test-systemd.sock");
+    let _ = std::fs::remove_file(path);
+
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let unix = UnixListener::bind(path).unwrap();
+
+    let mut child = spawn_activated(
+        "socket_activation_child",
+        &[tcp.as_raw_fd(), unix.as_raw_fd()],
+    );
+
+    let mut client = TcpStream::connect(tcp.local_addr().unwrap()).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("listener 0"));
+
+    let mut client = UnixStream::connect(path).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("listener 1"));
+
+    assert!(child.wait().unwrap().success());
+
+    // the socket file belongs to the service manager and must outlive the server
+    assert!(path.exists());
+    drop(unix);
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Runs in the child process started by `invalid_socket`, with a listener as file descriptor 3
+/// and a connected socket as file descriptor 4.
+#[test]
+#[ignore]
+fn invalid_socket_child() {
+    if std::env::var_os("TINY_HTTP_SYSTEMD_CHILD").is_none() {
+        return;
+    }
+
+    assert!(tiny_http::Listener::from_systemd().is_err());
+    assert!(tiny_http::Server::from_systemd(|_| None).is_err());
+
+    // the listener wasn't closed and is still available
+    let listener = unsafe { TcpListener::from_raw_fd(3) };
+    listener.accept().unwrap();
+}
+
+#[test]
+fn invalid_socket() {
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(tcp.local_addr().unwrap()).unwrap();
+
+    let mut child = spawn_activated(
+        "invalid_socket_child",
+        &[tcp.as_raw_fd(), client.as_raw_fd()],
+    );
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn not_socket_activated() {
+    assert!(tiny_http::Listener::from_systemd().unwrap().is_empty());
+    assert!(tiny_http::Server::from_systemd(|_| None).is_err());
+}
@@ -6,6 +6,7 @@ use std::{
     io::{Read, Write},
     os::unix::net::UnixStream,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 #[allow(dead_code)]
@@ -42,3 +43,293 @@ fn unix_basic_handling() {
     client.read_to_string(&mut content).unwrap();
     assert!(content.ends_with("hello world"));
 }
+
+#[test]
+fn unix_and_tcp_listeners() {
+    use tiny_http::{ConfigListenAddr, ListenerConfig, ServerConfig};
+
+    let server = tiny_http::Server::new(ServerConfig {
+        listeners: vec![
+            ListenerConfig::new(ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap()),
+            ListenerConfig::new(ConfigListenAddr::unix_from_path(
+                "/tmp/tiny-http-test-mixed.sock",
+            )),
+        ],
+        ..Default::default()
+    })
+    .unwrap();
+    let path: PathBuf = server.server_addrs()[1]
+        .clone()
+        .to_unix()
+        .unwrap()
+        .as_pathname()
+        .unwrap()
+        .into();
+    let mut client = UnixStream::connect(&path).unwrap();
+
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    assert_eq!(request.listener_index(), 1);
+    request
+        .respond(tiny_http::Response::from_string("hello world".to_owned()))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+
+    drop(server);
+    assert!(!path.exists());
+}
+
+#[test]
+fn unix_ignores_tcp_socket_options() {
+    let path = "/tmp/tiny-http-test-socket-options.sock";
+    let _ = std::fs::remove_file(path);
+
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::unix_from_path(path),
+        )],
+        socket_options: tiny_http::SocketOptions {
+            nodelay: true,
+            keepalive: Some(std::time::Duration::from_secs(60)),
+            backlog: Some(16),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .unwrap();
+
+    let mut client = UnixStream::connect(path).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello world".to_owned()))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+}
+
+#[test]
+fn unix_local_addr() {
+    let server =
+        tiny_http::Server::http_unix(Path::new("/tmp/tiny-http-test-local-addr.sock")).unwrap();
+    let mut client = UnixStream::connect("/tmp/tiny-http-test-local-addr.sock").unwrap();
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let request = server.recv().unwrap();
+    let local_addr = request.local_addr().unwrap().clone().to_unix().unwrap();
+    assert_eq!(
+        local_addr.as_pathname(),
+        Some(Path::new("/tmp/tiny-http-test-local-addr.sock"))
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn unix_peer_credentials() {
+    let server =
+        tiny_http::Server::http_unix(Path::new("/tmp/tiny-http-test-peer-cred.sock")).unwrap();
+    let mut client = UnixStream::connect("/tmp/tiny-http-test-peer-cred.sock").unwrap();
+
+    write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+    let request = server.recv().unwrap();
+    let credentials = request.peer_credentials().unwrap();
+    assert_eq!(credentials.pid as u32, std::process::id());
+}
+
+fn unix_listener(path: &str, options: tiny_http::UnixSocketOptions) -> tiny_http::ServerConfig {
+    tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::unix_from_path(path),
+        )
+        .with_unix_socket_options(options)],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn unix_remove_stale_socket() {
+    let path = "/tmp/tiny-http-test-stale.sock";
+    let _ = std::fs::remove_file(path);
+    // a listener that is dropped without cleaning up leaves a stale socket file behind
+    drop(std::os::unix::net::UnixListener::bind(path).unwrap());
+
+    assert!(tiny_http::Server::new(unix_listener(path, Default::default())).is_err());
+
+    let options = tiny_http::UnixSocketOptions {
+        remove_stale: true,
+        ..Default::default()
+    };
+    let server = tiny_http::Server::new(unix_listener(path, options.clone())).unwrap();
+
+    // the socket is alive, so it must not be taken over
+    assert!(tiny_http::Server::new(unix_listener(path, options)).is_err());
+    assert!(UnixStream::connect(path).is_ok());
+
+    drop(server);
+    assert!(!Path::new(path).exists());
+}
+
+#[test]
+fn unix_socket_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = "/tmp/tiny-http-test-mode.sock";
+    let options = tiny_http::UnixSocketOptions {
+        remove_stale: true,
+        mode: Some(0o600),
+        ..Default::default()
+    };
+    let _server = tiny_http::Server::new(unix_listener(path, options)).unwrap();
+
+    let mode = std::fs::metadata(path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn unix_keep_socket_on_drop() {
+    let path = "/tmp/tiny-http-test-keep.sock";
+    let options = tiny_http::UnixSocketOptions {
+        remove_stale: true,
+        remove_on_drop: false,
+        ..Default::default()
+    };
+    drop(tiny_http::Server::new(unix_listener(path, options)).unwrap());
+
+    assert!(Path::new(path).exists());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unix_replaced_socket_is_not_removed() {
+    let path = "/tmp/tiny-http-test-replaced.sock";
+    let _ = std::fs::remove_file(path);
+    let server = tiny_http::Server::http_unix(Path::new(path)).unwrap();
+
+    // another server takes over the path while the first one is still running
+    std::fs::remove_file(path).unwrap();
+    let replacement = std::os::unix::net::UnixListener::bind(path).unwrap();
+
+    drop(server);
+    assert!(Path::new(path).exists());
+
+    drop(replacement);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn unix_abstract_namespace() {
+    use std::os::linux::net::SocketAddrExt;
+
+    let server = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![tiny_http::ListenerConfig::new(
+            tiny_http::ConfigListenAddr::unix_abstract("tiny-http-test-abstract"),
+        )],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let addr =
+        std::os::unix::net::SocketAddr::from_abstract_name("tiny-http-test-abstract").unwrap();
+    let mut client = UnixStream::connect_addr(&addr).unwrap();
+    write!(
+        client,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let request = server.recv().unwrap();
+    request
+        .respond(tiny_http::Response::from_string("hello world".to_owned()))
+        .unwrap();
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("hello world"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn unix_listener_handoff() {
+    let path = "/tmp/tiny-http-test-handoff.sock";
+    let _ = std::fs::remove_file(path);
+
+    let old = tiny_http::Server::new(tiny_http::ServerConfig {
+        listeners: vec![
+            tiny_http::ListenerConfig::new(
+                tiny_http::ConfigListenAddr::from_socket_addrs("127.0.0.1:0").unwrap(),
+            ),
+            tiny_http::ListenerConfig::new(tiny_http::ConfigListenAddr::unix_from_path(path)),
+        ],
+        ..Default::default()
+    })
+    .unwrap();
+    let tcp_addr = old.server_addr().to_ip().unwrap();
+
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    old.send_listeners(&sender).unwrap();
+    let listeners = tiny_http::Listener::receive_listeners(&receiver).unwrap();
+    // the requests are answered by the policy of the configuration
+    let config = tiny_http::ServerConfig {
+        unanswered_requests: tiny_http::UnansweredRequestPolicy {
+            response: Some(Arc::new(|_| {
+                tiny_http::Response::from_string("new server").boxed()
+            })),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let new = tiny_http::Server::from_listeners_with_config(
+        listeners.into_iter().map(|l| (l, None)),
+        config,
+    )
+    .unwrap();
+    assert_eq!(new.server_addrs().len(), 2);
+    assert_eq!(new.server_addr().to_ip(), Some(tcp_addr));
+
+    old.stop_accepting();
+    drop(old);
+    assert!(Path::new(path).exists());
+
+    let mut tcp_client = std::net::TcpStream::connect(tcp_addr).unwrap();
+    let mut unix_client = UnixStream::connect(path).unwrap();
+    for index in 0..2 {
+        let client: &mut dyn Write = if index == 0 {
+            &mut tcp_client
+        } else {
+            &mut unix_client
+        };
+        write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+
+        let request = new.recv().unwrap();
+        assert_eq!(request.listener_index(), index);
+    }
+
+    let mut content = String::new();
+    tcp_client.read_to_string(&mut content).unwrap();
+    assert!(content.ends_with("new server"));
+
+    drop(new);
+    assert!(!Path::new(path).exists());
+}
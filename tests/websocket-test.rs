@@ -0,0 +1,431 @@
+#![cfg(feature = "websocket")]
+
+extern crate tiny_http;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+use tiny_http::websocket::{CloseFrame, Message, WebSocket, WebSocketConfig};
+
+#[allow(dead_code)]
+mod support;
+
+const HANDSHAKE: &str = "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                         Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                         Sec-WebSocket-Version: 13\r\n";
+
+/// Sends a masked frame, as clients do.
+fn send_frame(client: &mut TcpStream, first_byte: u8, payload: &[u8]) {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![first_byte];
+    match payload.len() {
+        length @ 0..=125 => frame.push(0x80 | length as u8),
+        length => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    client.write_all(&frame).unwrap();
+}
+
+/// Reads an unmasked frame, and returns its first byte and its payload.
+fn read_frame<R: Read>(reader: &mut R) -> (u8, Vec<u8>) {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).unwrap();
+    assert_eq!(head[1] & 0x80, 0, "frames from the server are not masked");
+    let length = match head[1] {
+        126 => {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length).unwrap();
+            u16::from_be_bytes(length) as usize
+        }
+        length => length as usize,
+    };
+    let mut payload = vec![0; length];
+    reader.read_exact(&mut payload).unwrap();
+    (head[0], payload)
+}
+
+/// Sends the handshake, and returns the socket of the server and the client reader.
+fn connect(config: &WebSocketConfig, extra_headers: &str) -> (WebSocket, BufReader<TcpStream>) {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(client, "{}{}\r\n", HANDSHAKE, extra_headers).unwrap();
+
+    let request = server.recv().unwrap();
+    assert!(request.is_websocket_upgrade());
+    let socket = request.accept_websocket(config).unwrap();
+
+    let mut reader = BufReader::new(client);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "HTTP/1.1 101 Switching Protocols\r\n");
+    while line != "\r\n" {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+    }
+    (socket, reader)
+}
+
+#[test]
+fn handshake() {
+    let (server, mut client) = support::new_one_server_one_client();
+    write!(
+        client,
+        "{}Sec-WebSocket-Protocol: superchat, chat\r\n\r\n",
+        HANDSHAKE
+    )
+    .unwrap();
+
+    let config = WebSocketConfig::default().with_protocols(["chat", "superchat"]);
+    let socket = server.recv().unwrap().accept_websocket(&config).unwrap();
+    assert_eq!(socket.protocol(), Some("chat"));
+    drop(socket);
+
+    let mut content = String::new();
+    client.read_to_string(&mut content).unwrap();
+    assert!(content.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(content.contains("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    assert!(content.contains("sec-websocket-protocol: chat\r\n"));
+    assert!(content.contains("upgrade: websocket\r\n"));
+}
+
+#[test]
+fn invalid_handshakes() {
+    let requests = [
+        (
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            "400",
+        ),
+        (
+            "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: short\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            "400",
+        ),
+        (
+            "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n",
+            "426",
+        ),
+    ];
+
+    for (request, status) in requests {
+        let (server, mut client) = support::new_one_server_one_client();
+        client.write_all(request.as_bytes()).unwrap();
+
+        let err = server
+            .recv()
+            .unwrap()
+            .accept_websocket(&WebSocketConfig::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        assert!(content.starts_with(&format!("HTTP/1.1 {}", status)));
+        if status == "426" {
+            assert!(content.contains("sec-websocket-version: 13\r\n"));
+        }
+    }
+}
+
+#[test]
+fn messages() {
+    let (mut socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+
+    send_frame(&mut client, 0x81, b"hello");
+    assert_eq!(socket.recv().unwrap(), Message::Text("hello".to_owned()));
+
+    // fragmented, with a ping in the middle
+    send_frame(&mut client, 0x02, &[1, 2]);
+    send_frame(&mut client, 0x89, b"ping");
+    send_frame(&mut client, 0x80, &[3]);
+    assert_eq!(socket.recv().unwrap(), Message::Ping(b"ping".to_vec()));
+    assert_eq!(read_frame(&mut reader), (0x8A, b"ping".to_vec()));
+    assert_eq!(socket.recv().unwrap(), Message::Binary(vec![1, 2, 3]));
+
+    let long = "x".repeat(1000);
+    socket.send(Message::Text(long.clone())).unwrap();
+    assert_eq!(read_frame(&mut reader), (0x81, long.into_bytes()));
+    socket.send(Message::Binary(vec![4, 5])).unwrap();
+    assert_eq!(read_frame(&mut reader), (0x82, vec![4, 5]));
+}
+
+#[test]
+fn outgoing_fragments() {
+    let config = WebSocketConfig::default().with_fragment_size(4);
+    let (mut socket, mut reader) = connect(&config, "");
+
+    socket
+        .send(Message::Text("hello world".to_owned()))
+        .unwrap();
+    assert_eq!(read_frame(&mut reader), (0x01, b"hell".to_vec()));
+    assert_eq!(read_frame(&mut reader), (0x00, b"o wo".to_vec()));
+    assert_eq!(read_frame(&mut reader), (0x80, b"rld".to_vec()));
+}
+
+#[test]
+fn close_from_client() {
+    let (mut socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+
+    send_frame(&mut client, 0x88, b"\x03\xe8bye");
+    assert_eq!(
+        socket.recv().unwrap(),
+        Message::Close(Some(CloseFrame {
+            code: 1000,
+            reason: "bye".to_owned()
+        }))
+    );
+    assert_eq!(read_frame(&mut reader), (0x88, vec![0x03, 0xe8]));
+
+    assert_eq!(
+        socket.recv().unwrap_err().kind(),
+        std::io::ErrorKind::NotConnected
+    );
+    assert!(socket.send(Message::Text("late".to_owned())).is_err());
+}
+
+#[test]
+fn close_from_server() {
+    let (socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+    let (mut socket_reader, socket_writer) = socket.split();
+
+    socket_writer.close(1001, "going away").unwrap();
+    let (first_byte, payload) = read_frame(&mut reader);
+    assert_eq!(first_byte, 0x88);
+    assert_eq!(&payload[..2], &[0x03, 0xe9]);
+    assert_eq!(&payload[2..], b"going away");
+
+    // the answer of the client ends the handshake
+    send_frame(&mut client, 0x88, &payload[..2]);
+    assert!(matches!(
+        socket_reader.recv().unwrap(),
+        Message::Close(Some(CloseFrame { code: 1001, .. }))
+    ));
+}
+
+#[test]
+fn send_from_another_thread() {
+    let (socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let (mut socket_reader, socket_writer) = socket.split();
+
+    let receiver = thread::spawn(move || socket_reader.recv().unwrap());
+    socket_writer
+        .send(Message::Text("while waiting".to_owned()))
+        .unwrap();
+    assert_eq!(read_frame(&mut reader), (0x81, b"while waiting".to_vec()));
+
+    let mut client = reader.get_ref().try_clone().unwrap();
+    send_frame(&mut client, 0x81, b"done");
+    assert_eq!(receiver.join().unwrap(), Message::Text("done".to_owned()));
+}
+
+#[test]
+fn protocol_errors() {
+    // unmasked frame
+    let (mut socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    reader
+        .get_mut()
+        .write_all(&[0x81, 0x02, b'h', b'i'])
+        .unwrap();
+    let err = socket.recv().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(read_frame(&mut reader).1[..2], 1002u16.to_be_bytes());
+
+    // invalid UTF-8
+    let (mut socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+    send_frame(&mut client, 0x81, &[0xff, 0xfe]);
+    assert!(socket.recv().is_err());
+    assert_eq!(read_frame(&mut reader).1[..2], 1007u16.to_be_bytes());
+
+    // continuation without a message
+    let (mut socket, mut reader) = connect(&WebSocketConfig::default(), "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+    send_frame(&mut client, 0x80, b"data");
+    assert!(socket.recv().is_err());
+    assert_eq!(read_frame(&mut reader).1[..2], 1002u16.to_be_bytes());
+}
+
+#[test]
+fn size_limits() {
+    let config = WebSocketConfig::default()
+        .with_max_frame_size(100)
+        .with_max_message_size(150);
+
+    let (mut socket, mut reader) = connect(&config, "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+    send_frame(&mut client, 0x82, &[0; 101]);
+    assert!(socket.recv().is_err());
+    assert_eq!(read_frame(&mut reader).1[..2], 1009u16.to_be_bytes());
+
+    let (mut socket, mut reader) = connect(&config, "");
+    let mut client = reader.get_ref().try_clone().unwrap();
+    send_frame(&mut client, 0x02, &[0; 100]);
+    send_frame(&mut client, 0x80, &[0; 51]);
+    assert!(socket.recv().is_err());
+    assert_eq!(read_frame(&mut reader).1[..2], 1009u16.to_be_bytes());
+}
+
+#[cfg(feature = "websocket-deflate")]
+mod deflate {
+    use super::*;
+
+    use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+    use tiny_http::websocket::DeflateConfig;
+
+    const OFFER: &str = "Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n";
+
+    /// Returns the `Sec-WebSocket-Extensions` header sent back for an offer.
+    fn negotiate(config: DeflateConfig, offer: &str) -> Option<String> {
+        let (server, mut client) = support::new_one_server_one_client();
+        write!(
+            client,
+            "{}Sec-WebSocket-Extensions: {}\r\n\r\n",
+            HANDSHAKE, offer
+        )
+        .unwrap();
+
+        let config = WebSocketConfig::default().with_deflate(config);
+        drop(server.recv().unwrap().accept_websocket(&config).unwrap());
+
+        let mut content = String::new();
+        client.read_to_string(&mut content).unwrap();
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix("sec-websocket-extensions: "))
+            .map(str::to_owned)
+    }
+
+    #[test]
+    fn negotiation() {
+        let default = DeflateConfig::default;
+        assert_eq!(
+            negotiate(default(), "permessage-deflate").as_deref(),
+            Some("permessage-deflate")
+        );
+        assert_eq!(negotiate(default(), "x-webkit-deflate-frame"), None);
+        // smaller windows need zlib
+        let small_window = negotiate(
+            default(),
+            "permessage-deflate; server_max_window_bits=10; client_no_context_takeover",
+        );
+        if cfg!(feature = "zlib-rs") {
+            assert_eq!(
+                small_window.as_deref(),
+                Some("permessage-deflate; client_no_context_takeover; server_max_window_bits=10")
+            );
+        } else {
+            assert_eq!(small_window, None);
+        }
+        assert_eq!(
+            negotiate(
+                default(),
+                "permessage-deflate; server_max_window_bits=15; client_no_context_takeover"
+            )
+            .as_deref(),
+            Some("permessage-deflate; client_no_context_takeover; server_max_window_bits=15")
+        );
+
+        // the first acceptable offer is used
+        assert_eq!(
+            negotiate(
+                default(),
+                "permessage-deflate; unknown, permessage-deflate; server_max_window_bits=8, \
+                 permessage-deflate; server_no_context_takeover"
+            )
+            .as_deref(),
+            Some("permessage-deflate; server_no_context_takeover")
+        );
+
+        // client windows are only limited for clients that allow it
+        let config = || {
+            default()
+                .with_client_max_window_bits(10)
+                .with_server_no_context_takeover()
+        };
+        assert_eq!(
+            negotiate(config(), "permessage-deflate; client_max_window_bits").as_deref(),
+            Some("permessage-deflate; server_no_context_takeover; client_max_window_bits=10")
+        );
+        assert_eq!(
+            negotiate(config(), "permessage-deflate").as_deref(),
+            Some("permessage-deflate; server_no_context_takeover")
+        );
+    }
+
+    #[test]
+    fn compressed_messages() {
+        let config = WebSocketConfig::default().with_deflate(DeflateConfig::default());
+        let (mut socket, mut reader) = connect(&config, OFFER);
+        let mut client = reader.get_ref().try_clone().unwrap();
+
+        // examples of section 7.2.3 of RFC 7692, the second one using the first as context
+        send_frame(
+            &mut client,
+            0xC1,
+            &[0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00],
+        );
+        assert_eq!(socket.recv().unwrap(), Message::Text("Hello".to_owned()));
+        send_frame(&mut client, 0xC1, &[0xf2, 0x00, 0x11, 0x00, 0x00]);
+        assert_eq!(socket.recv().unwrap(), Message::Text("Hello".to_owned()));
+
+        // fragmented, and uncompressed
+        send_frame(&mut client, 0x41, &[0xf2, 0x48, 0xcd]);
+        send_frame(&mut client, 0x80, &[0xc9, 0xc9, 0x07, 0x00]);
+        assert_eq!(socket.recv().unwrap(), Message::Text("Hello".to_owned()));
+        send_frame(&mut client, 0x81, b"plain");
+        assert_eq!(socket.recv().unwrap(), Message::Text("plain".to_owned()));
+
+        let text = "compressible ".repeat(100);
+        socket.send(Message::Text(text.clone())).unwrap();
+        let (first_byte, mut payload) = read_frame(&mut reader);
+        assert_eq!(first_byte, 0xC1);
+        assert!(payload.len() < 100);
+
+        payload.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+        let mut decompress = Decompress::new(false);
+        let mut output = Vec::with_capacity(text.len() * 2);
+        decompress
+            .decompress_vec(&payload, &mut output, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(output, text.as_bytes());
+    }
+
+    #[test]
+    fn decompression_bomb() {
+        let config = WebSocketConfig::default()
+            .with_max_message_size(10_000)
+            .with_deflate(DeflateConfig::default());
+        let (mut socket, mut reader) = connect(&config, OFFER);
+        let mut client = reader.get_ref().try_clone().unwrap();
+
+        let mut compress = Compress::new(Compression::best(), false);
+        let mut bomb = Vec::with_capacity(10_000);
+        compress
+            .compress_vec(&[0; 1_000_000], &mut bomb, FlushCompress::Sync)
+            .unwrap();
+        assert!(bomb.len() < 10_000);
+
+        send_frame(&mut client, 0xC2, &bomb);
+        assert!(socket.recv().is_err());
+        assert_eq!(read_frame(&mut reader).1[..2], 1009u16.to_be_bytes());
+    }
+
+    #[test]
+    fn compressed_control_frame() {
+        let config = WebSocketConfig::default().with_deflate(DeflateConfig::default());
+        let (mut socket, mut reader) = connect(&config, OFFER);
+        let mut client = reader.get_ref().try_clone().unwrap();
+
+        send_frame(&mut client, 0xC9, b"ping");
+        assert!(socket.recv().is_err());
+        assert_eq!(read_frame(&mut reader).1[..2], 1002u16.to_be_bytes());
+    }
+}